@@ -0,0 +1,196 @@
+// comparing typed answers against a card's answer.
+
+use std::collections::HashSet;
+
+// drops trailing parenthesized groups, e.g. `run (verb)` -> `run`. an answer
+// that is nothing but parentheses is kept as is.
+pub fn strip_parens(s: &str) -> &str {
+    let mut s = s.trim_end();
+    while s.ends_with(')') {
+        let mut depth = 0;
+        let Some(open) = s.char_indices().rev().find_map(|(i, c)| {
+            match c {
+                ')' => depth += 1,
+                '(' => depth -= 1,
+                _ => {}
+            }
+            (depth == 0).then_some(i)
+        }) else {
+            break;
+        };
+        let stripped = s[..open].trim_end();
+        if stripped.is_empty() {
+            break;
+        }
+        s = stripped;
+    }
+    s
+}
+
+// case and runs of whitespace never matter. each enabled normalizer is applied on top.
+pub fn normalize(s: &str, strip: bool) -> String {
+    let s = if strip { strip_parens(s) } else { s };
+    s.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+pub fn matches(typed: &str, expected: &str, strip: bool) -> bool {
+    normalize(typed, strip) == normalize(expected, strip)
+}
+
+// for answers that list items split by `sep`: the expected items that were typed,
+// in any order, and the ones that were missed.
+pub fn match_items<'a>(
+    typed: &str,
+    expected: &'a str,
+    sep: &str,
+    strip: bool,
+) -> (Vec<&'a str>, Vec<&'a str>) {
+    let typed = typed
+        .split(sep)
+        .map(|item| normalize(item, strip))
+        .collect::<HashSet<_>>();
+    expected
+        .split(sep)
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .partition(|item| typed.contains(&normalize(item, strip)))
+}
+
+// the answer with each character as `_` and whitespace kept, e.g. `New York` ->
+// `___ ____`. counts graphemes, not bytes, so `こんにちは` gets 5.
+pub fn scaffold(answer: &str) -> String {
+    let mut scaffold = String::new();
+    let mut joined = false;
+    for c in answer.trim().chars() {
+        if c.is_whitespace() {
+            scaffold.push(c);
+        } else if !joined && !extends_grapheme(c) {
+            scaffold.push('_');
+        }
+        // the character after a zero width joiner belongs to the same emoji.
+        joined = c == '\u{200d}';
+    }
+    scaffold
+}
+
+// columns taken up in a terminal: east asian wide characters and most emoji take
+// two, characters extending a grapheme none. like extends_grapheme, a close enough
+// subset of the unicode rules.
+pub fn display_width(s: &str) -> usize {
+    let mut joined = false;
+    let mut width = 0;
+    for c in s.chars() {
+        if !joined && !extends_grapheme(c) {
+            width += if is_wide(c) { 2 } else { 1 };
+        }
+        joined = c == '\u{200d}';
+    }
+    width
+}
+
+fn is_wide(c: char) -> bool {
+    matches!(
+        c,
+        '\u{1100}'..='\u{115f}'
+            | '\u{2e80}'..='\u{303e}'
+            | '\u{3041}'..='\u{33ff}'
+            | '\u{3400}'..='\u{4dbf}'
+            | '\u{4e00}'..='\u{9fff}'
+            | '\u{a000}'..='\u{a4cf}'
+            | '\u{ac00}'..='\u{d7a3}'
+            | '\u{f900}'..='\u{faff}'
+            | '\u{fe30}'..='\u{fe4f}'
+            | '\u{ff00}'..='\u{ff60}'
+            | '\u{ffe0}'..='\u{ffe6}'
+            | '\u{1f300}'..='\u{1f64f}'
+            | '\u{1f900}'..='\u{1f9ff}'
+            | '\u{20000}'..='\u{3fffd}'
+    )
+}
+
+// combining marks, variation selectors and joiners, which don't start a
+// grapheme of their own. a close enough subset of the unicode rules for answers.
+fn extends_grapheme(c: char) -> bool {
+    matches!(
+        c,
+        '\u{300}'..='\u{36f}'
+            | '\u{1ab0}'..='\u{1aff}'
+            | '\u{1dc0}'..='\u{1dff}'
+            | '\u{200d}'
+            | '\u{20d0}'..='\u{20ff}'
+            | '\u{3099}'..='\u{309a}'
+            | '\u{fe00}'..='\u{fe0f}'
+            | '\u{fe20}'..='\u{fe2f}'
+            | '\u{1f3fb}'..='\u{1f3ff}'
+    )
+}
+
+#[cfg(test)]
+mod test_answer {
+    use super::*;
+
+    #[test]
+    fn test_display_width() {
+        assert_eq!(display_width("Oslo"), 4);
+        assert_eq!(display_width("こんにちは世界"), 14);
+        assert_eq!(display_width("서울"), 4);
+        assert_eq!(display_width("cafe\u{301}"), 4);
+        assert_eq!(
+            display_width("\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467} ok"),
+            5
+        );
+    }
+
+    #[test]
+    fn test_scaffold() {
+        assert_eq!(scaffold("New York"), "___ ____");
+        assert_eq!(scaffold(" Oslo "), "____");
+        assert_eq!(scaffold("こんにちは世界"), "_______");
+        // e + combining acute, and a family emoji joined by zero width joiners.
+        assert_eq!(scaffold("cafe\u{301}"), "____");
+        assert_eq!(
+            scaffold("\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467} ok"),
+            "_ __"
+        );
+        assert_eq!(scaffold(""), "");
+    }
+
+    #[test]
+    fn test_strip_parens() {
+        assert_eq!(strip_parens("run (verb)"), "run");
+        assert_eq!(strip_parens("run (verb) (informal) "), "run");
+        assert_eq!(strip_parens("run (to (go) fast)"), "run");
+        assert_eq!(strip_parens("(a) run"), "(a) run");
+        assert_eq!(strip_parens("(verb)"), "(verb)");
+        assert_eq!(strip_parens("run)"), "run)");
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("run", "run (verb)", true));
+        assert!(matches("run (verb)", "run (verb)", true));
+        assert!(matches("  Run ", "run (verb)", true));
+        assert!(!matches("run", "run (verb)", false));
+        assert!(!matches("walk", "run (verb)", true));
+    }
+
+    #[test]
+    fn test_match_items() {
+        let expected = "war; famine; plague (disease)";
+        assert_eq!(
+            match_items("Plague; war", expected, ";", true),
+            (vec!["war", "plague (disease)"], vec!["famine"])
+        );
+        assert_eq!(
+            match_items("plague; war; flood", expected, ";", false),
+            (vec!["war"], vec!["famine", "plague (disease)"])
+        );
+        assert_eq!(
+            match_items("", "a;;b", ";", false),
+            (vec![], vec!["a", "b"])
+        );
+    }
+}
@@ -0,0 +1,113 @@
+// the command that speaks the cues with --speak-lang. each word of the template
+// is one argument, so `{text}` stays a single argument even with spaces in it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioCommand {
+    pub template: String,
+    pub rate: Option<String>,
+    pub voice: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AudioErr {
+    EmptyCommand,
+    // e.g. `{rate}` without --audio-rate.
+    MissingValue(String),
+    UnknownPlaceholder(String),
+}
+
+impl Default for AudioCommand {
+    fn default() -> Self {
+        AudioCommand {
+            template: "trans -speak -no-translate -s {lang} {text}".to_string(),
+            rate: None,
+            voice: None,
+        }
+    }
+}
+
+impl AudioCommand {
+    pub fn argv(&self, lang: &str, text: &str) -> Result<Vec<String>, AudioErr> {
+        let argv = self
+            .template
+            .split_whitespace()
+            .map(|word| self.substitute(word, lang, text))
+            .collect::<Result<Vec<_>, _>>()?;
+        if argv.is_empty() {
+            return Err(AudioErr::EmptyCommand);
+        }
+        Ok(argv)
+    }
+
+    // every placeholder the template uses has a value.
+    pub fn validate(&self) -> Result<(), AudioErr> {
+        self.argv("", "").map(|_| ())
+    }
+
+    fn substitute(&self, word: &str, lang: &str, text: &str) -> Result<String, AudioErr> {
+        let mut arg = String::new();
+        let mut rest = word;
+        while let Some((before, after)) = rest.split_once('{') {
+            let Some((name, after)) = after.split_once('}') else {
+                break;
+            };
+            let value = match name {
+                "lang" => Some(lang),
+                "text" => Some(text),
+                "rate" => self.rate.as_deref(),
+                "voice" => self.voice.as_deref(),
+                _ => return Err(AudioErr::UnknownPlaceholder(name.to_string())),
+            };
+            arg += before;
+            arg += value.ok_or_else(|| AudioErr::MissingValue(name.to_string()))?;
+            rest = after;
+        }
+        arg += rest;
+        Ok(arg)
+    }
+}
+
+#[cfg(test)]
+mod test_audio {
+    use super::*;
+
+    #[test]
+    fn test_argv() {
+        assert_eq!(
+            AudioCommand::default().argv("fr", "le chat").unwrap(),
+            vec!["trans", "-speak", "-no-translate", "-s", "fr", "le chat"]
+        );
+
+        let espeak = AudioCommand {
+            template: "espeak -s {rate} -v {voice}+f2 {text}".to_string(),
+            rate: Some("120".to_string()),
+            voice: Some("fr".to_string()),
+        };
+        assert_eq!(
+            espeak.argv("fr", "le chat").unwrap(),
+            vec!["espeak", "-s", "120", "-v", "fr+f2", "le chat"]
+        );
+        assert!(espeak.validate().is_ok());
+
+        let no_rate = AudioCommand {
+            rate: None,
+            ..espeak.clone()
+        };
+        assert_eq!(
+            no_rate.validate(),
+            Err(AudioErr::MissingValue("rate".to_string()))
+        );
+        let unknown = AudioCommand {
+            template: "say {pitch} {text}".to_string(),
+            ..espeak
+        };
+        assert_eq!(
+            unknown.validate(),
+            Err(AudioErr::UnknownPlaceholder("pitch".to_string()))
+        );
+        let empty = AudioCommand {
+            template: " ".to_string(),
+            ..AudioCommand::default()
+        };
+        assert_eq!(empty.validate(), Err(AudioErr::EmptyCommand));
+    }
+}
@@ -0,0 +1,123 @@
+use std::path::Path;
+
+use chrono::NaiveDate;
+
+// the new cards allowed on a given day, kept in a small state file with one
+// `date,allowance,used,key` line per deck or group of decks sharing --max-new.
+// whatever is left unused at the end of a day, skipped days included, rolls over
+// to the next, up to a cap.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct NewBudget {
+    pub allowance: usize,
+    pub used: usize,
+}
+
+impl NewBudget {
+    pub fn read(
+        path: &Path,
+        key: &str,
+        date: NaiveDate,
+        daily: usize,
+        max_rollover: usize,
+    ) -> NewBudget {
+        let fresh = |carried: usize| NewBudget {
+            allowance: daily + carried.min(max_rollover),
+            used: 0,
+        };
+        let contents = std::fs::read_to_string(path).unwrap_or_default();
+        let Some((day, last)) = contents
+            .lines()
+            .filter_map(NewBudget::parse)
+            .find(|(_, _, line_key)| *line_key == key)
+            .map(|(day, budget, _)| (day, budget))
+        else {
+            return fresh(0);
+        };
+        let days = (date - day).num_days();
+        if days == 0 {
+            return last;
+        }
+        if days < 0 {
+            return fresh(0);
+        }
+        let skipped = daily * (days as usize - 1);
+        fresh(last.remaining() + skipped)
+    }
+
+    fn parse(line: &str) -> Option<(NaiveDate, NewBudget, &str)> {
+        let mut fields = line.splitn(4, ',');
+        let day = fields.next()?.parse().ok()?;
+        let allowance = fields.next()?.parse().ok()?;
+        let used = fields.next()?.parse().ok()?;
+        Some((day, NewBudget { allowance, used }, fields.next()?))
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.allowance.saturating_sub(self.used)
+    }
+
+    // replaces the line for `key`, keeping the others.
+    pub fn save(&self, path: &Path, key: &str, date: NaiveDate) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut contents = std::fs::read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .filter(|line| NewBudget::parse(line).is_some_and(|(_, _, line_key)| line_key != key))
+            .map(|line| format!("{}\n", line))
+            .collect::<String>();
+        contents += &format!("{},{},{},{}\n", date, self.allowance, self.used, key);
+        std::fs::write(path, contents)
+    }
+}
+
+#[cfg(test)]
+mod test_budget {
+    use super::*;
+
+    #[test]
+    fn test_rollover() {
+        const STATE: &str = "tests/test_new_budget_copy";
+        let path = Path::new(STATE);
+        let _ = std::fs::remove_file(path);
+        let day = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+
+        let mut budget = NewBudget::read(path, "a", day(1), 10, 15);
+        assert_eq!(
+            budget,
+            NewBudget {
+                allowance: 10,
+                used: 0
+            }
+        );
+        budget.used += 4;
+        budget.save(path, "a", day(1)).unwrap();
+        // a second session the same day continues where the first left off.
+        assert_eq!(NewBudget::read(path, "a", day(1), 10, 15).remaining(), 6);
+        // other decks keep their own budget.
+        assert_eq!(NewBudget::read(path, "b", day(1), 10, 15).remaining(), 10);
+        NewBudget::read(path, "b", day(1), 5, 15)
+            .save(path, "b", day(1))
+            .unwrap();
+
+        // 6 left over on the 1st.
+        let budget = NewBudget::read(path, "a", day(2), 10, 15);
+        assert_eq!(
+            budget,
+            NewBudget {
+                allowance: 16,
+                used: 0
+            }
+        );
+        budget.save(path, "a", day(2)).unwrap();
+        assert_eq!(std::fs::read_to_string(path).unwrap().lines().count(), 2);
+        assert_eq!(NewBudget::read(path, "b", day(2), 5, 15).allowance, 10);
+
+        // the 3rd is skipped: all 16 of the 2nd and 10 of the 3rd roll over, up to 15.
+        assert_eq!(NewBudget::read(path, "a", day(4), 10, 15).allowance, 25);
+        assert_eq!(NewBudget::read(path, "a", day(4), 10, 30).allowance, 36);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
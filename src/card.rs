@@ -3,15 +3,47 @@ use std::{
     str::FromStr,
 };
 
-use chrono::{Date, Local, TimeZone, Timelike};
+use chrono::{DateTime, Days, Local, NaiveDate, TimeZone, Timelike};
 
-const INIT_TICKS: usize = 2;
+use crate::{
+    config::{Config, RelearnNew, REQUEUE_MINUTES},
+    scheduler::Grade,
+};
+
+pub const INIT_TICKS: usize = 2;
 
 #[derive(Debug, PartialEq)]
 pub struct Card {
     pub id: usize,
     pub answer: String,
     pub cues: Vec<String>,
+    // from an optional last field made up of `@tag` words, e.g. `| @geo @europe`.
+    pub tags: Vec<String>,
+    // from an optional `%`-prefixed field before the tags, shown once the card is graded.
+    pub note: Option<String>,
+}
+
+// set with a `@prio:high` or `@prio:low` tag. sorts high priority first.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+pub enum Priority {
+    High,
+    Normal,
+    Low,
+}
+
+impl Card {
+    pub fn priority(&self) -> Priority {
+        match self.tags.iter().find_map(|tag| tag.strip_prefix("prio:")) {
+            Some("high") => Priority::High,
+            Some("low") => Priority::Low,
+            _ => Priority::Normal,
+        }
+    }
+
+    // a `@pin` tag puts the card first in every session until the tag is removed.
+    pub fn is_pinned(&self) -> bool {
+        self.tags.iter().any(|tag| tag == "pin")
+    }
 }
 
 #[derive(Debug, PartialEq, Copy, Clone)]
@@ -20,6 +52,12 @@ pub struct Status {
     pub timestamp: i64,
     pub factor: f64,
     pub ticks: usize,
+    // index into the deck's learning steps. only meaningful while `is_new`.
+    pub step: usize,
+    // number of correct reviews that completed a turn.
+    pub reviews: usize,
+    // number of wrong reviews of the card once learned.
+    pub lapses: usize,
 }
 
 impl Status {
@@ -29,15 +67,31 @@ impl Status {
             timestamp: Local::now().timestamp(),
             factor: 0.0,
             ticks: INIT_TICKS,
+            step: 0,
+            reviews: 0,
+            lapses: 0,
         }
     }
 
-    pub fn due_date(&self) -> Date<Local> {
-        Local.timestamp(self.timestamp, 0).date()
+    pub fn due_date(&self) -> NaiveDate {
+        Local.timestamp_opt(self.timestamp, 0).unwrap().date_naive()
+    }
+
+    // the moment the card comes due: the start of its due date once learned, the
+    // exact timestamp while it is still in its learning steps.
+    pub fn due_at(&self) -> i64 {
+        if self.is_new() {
+            return self.timestamp;
+        }
+        let midnight = self.due_date().and_hms_opt(0, 0, 0).unwrap();
+        Local
+            .from_local_datetime(&midnight)
+            .earliest()
+            .map_or(self.timestamp, |due| due.timestamp())
     }
 
     pub fn days_left(&self) -> i64 {
-        (self.due_date() - Local::today()).num_days()
+        (self.due_date() - Local::now().date_naive()).num_days()
     }
 
     pub fn is_new(&self) -> bool {
@@ -45,39 +99,109 @@ impl Status {
     }
 
     pub fn is_due(&self) -> bool {
-        self.ticks > 0 && self.due_date() <= Local::today()
+        self.is_due_within(0)
+    }
+
+    // also counts learned cards due in the next `days` days, for reviewing ahead.
+    // their next interval still starts from the original due date in `update`.
+    pub fn is_due_within(&self, days: u32) -> bool {
+        self.ticks > 0
+            && self.due_date() <= Local::now().date_naive() + Days::new(days as u64)
+            && (!self.is_new() || self.timestamp <= Local::now().timestamp())
     }
 
-    // shall ONLY be called if self.ticks >= 1.
-    pub fn update(&mut self, correct: bool, randomize: bool) -> usize {
-        if !correct && self.is_new() {
-            self.ticks = INIT_TICKS;
+    // shall ONLY be called if self.ticks >= 1. `now` is when the card was
+    // answered, earlier than now when replaying a history.
+    pub fn update(
+        &mut self,
+        correct: bool,
+        randomize: bool,
+        config: &Config,
+        now: DateTime<Local>,
+    ) -> usize {
+        if self.is_new() {
+            let steps = &config.learning_steps;
+            if correct {
+                self.step += 1;
+            } else {
+                self.step = match config.relearn_new {
+                    RelearnNew::Reset => 0,
+                    RelearnNew::AddOne => self.step.saturating_sub(1),
+                };
+            }
+            if self.step < steps.len() {
+                self.ticks = steps.len() - self.step;
+                // short steps are simply re-queued within the session.
+                if steps[self.step] >= REQUEUE_MINUTES {
+                    self.timestamp = now.timestamp() + 60 * steps[self.step] as i64;
+                }
+                return self.ticks;
+            }
+            self.step = 0;
+            self.ticks = 0;
         } else {
             self.ticks -= 1;
         }
 
         if self.ticks == 0 {
+            // graduating from the learning steps counts as a review, only a
+            // wrong answer once learned as a lapse.
             if correct {
-                self.factor *= 2.0;
-            } else {
-                self.factor /= 2.0;
+                self.reviews += 1;
+            } else if !self.is_new() {
+                self.lapses += 1;
             }
-            self.factor = self.factor.max(1.0);
+            config.scheduler.update(self, Grade::from(correct));
             if randomize {
                 self.factor *= 1.0 + (0.2 * rand::random::<f64>());
             }
 
-            let now = Local::now();
-            if self.due_date() < now.date() {
+            if self.due_date() < now.date_naive() {
                 self.timestamp = now.with_hour(0).unwrap().timestamp()
             }
-            self.timestamp += (86400.0 * self.factor) as i64;
+            self.timestamp += (86400.0 * self.factor * config.interval_mod) as i64;
         }
 
         self.ticks
     }
 }
 
+impl std::fmt::Display for Card {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} | {}", self.id, self.answer)?;
+        for cue in self.cues.iter() {
+            write!(f, " | {}", cue)?;
+        }
+        if let Some(note) = &self.note {
+            write!(f, " | %{}", note)?;
+        }
+        if !self.tags.is_empty() {
+            write!(f, " |")?;
+            for tag in self.tags.iter() {
+                write!(f, " @{}", tag)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// the log line format. `step`, `reviews` and `lapses` are only written when nonzero.
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // e.g. `{:.4}` for more decimals of the factor.
+        let precision = f.precision().unwrap_or(2);
+        write!(
+            f,
+            "{},{},{:.*}",
+            self.id, self.timestamp, precision, self.factor
+        )?;
+        if self.step > 0 || self.reviews > 0 || self.lapses > 0 {
+            write!(f, ",{},{},{}", self.step, self.reviews, self.lapses)?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub enum CardParseErr {
     NotEnoughFields,
@@ -91,6 +215,11 @@ pub enum StatusParseErr {
     InvalidId(ParseIntError),
     InvalidTimestamp(ParseIntError),
     InvalidFactor(ParseFloatError),
+    NegativeFactor,
+    NonFiniteFactor,
+    InvalidStep(ParseIntError),
+    InvalidReviews(ParseIntError),
+    InvalidLapses(ParseIntError),
     EmptyStr,
 }
 
@@ -98,6 +227,15 @@ impl FromStr for Card {
     type Err = CardParseErr;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Card::parse_with_width(s, None)
+    }
+}
+
+impl Card {
+    // with a width, that many fields after the answer are cues even when they
+    // start with `%` or `@`, so that only fields past the deck's columns are read
+    // as the note and tags.
+    pub fn parse_with_width(s: &str, width: Option<usize>) -> Result<Card, CardParseErr> {
         if s.is_empty() {
             return Err(CardParseErr::EmptyStr);
         }
@@ -117,10 +255,51 @@ impl FromStr for Card {
         if answer.is_empty() {
             return Err(CardParseErr::NotEnoughFields);
         }
-        let cues = it.map(|cue| cue.trim().to_string()).collect();
+        let mut cues = it.map(|cue| cue.trim().to_string()).collect::<Vec<_>>();
+        let extra = |cues: &Vec<String>| width.is_none_or(|width| cues.len() > width);
+        let tags = match cues.last() {
+            Some(last) if last.starts_with('@') && extra(&cues) => {
+                let tags = last
+                    .split_whitespace()
+                    .map(|tag| tag.trim_start_matches('@').to_string())
+                    .filter(|tag| !tag.is_empty())
+                    .collect();
+                cues.pop();
+                tags
+            }
+            _ => vec![],
+        };
+        let note = match cues.last() {
+            Some(last) if last.starts_with('%') && extra(&cues) => {
+                let note = last.trim_start_matches('%').trim().to_string();
+                cues.pop();
+                Some(note)
+            }
+            _ => None,
+        };
+
+        Ok(Card {
+            id,
+            answer,
+            cues,
+            tags,
+            note,
+        })
+    }
+}
 
-        Ok(Card { id, answer, cues })
+// how many cues Card::parse_with_width would find on the line, without building
+// the card.
+pub fn count_cues(line: &str, width: Option<usize>) -> usize {
+    let mut fields = line.split('|').skip(2).map(str::trim).collect::<Vec<_>>();
+    let extra = |fields: &Vec<&str>| width.is_none_or(|width| fields.len() > width);
+    if fields.last().is_some_and(|last| last.starts_with('@')) && extra(&fields) {
+        fields.pop();
     }
+    if fields.last().is_some_and(|last| last.starts_with('%')) && extra(&fields) {
+        fields.pop();
+    }
+    fields.len()
 }
 
 impl FromStr for Status {
@@ -145,12 +324,42 @@ impl FromStr for Status {
             .parse()
             .map_err(StatusParseErr::InvalidTimestamp)?;
 
-        let factor = it
+        let factor: f64 = it
             .next()
             .ok_or(StatusParseErr::NotEnoughFields)?
             .trim()
             .parse()
             .map_err(StatusParseErr::InvalidFactor)?;
+        if !factor.is_finite() {
+            return Err(StatusParseErr::NonFiniteFactor);
+        }
+        if factor < 0.0 {
+            return Err(StatusParseErr::NegativeFactor);
+        }
+        // a reviewed card never drops below 1.0, and anything smaller would be
+        // written back as 0.00 and turn the card new again.
+        let factor = if factor > 0.0 { factor.max(1.0) } else { 0.0 };
+
+        let step = match it.next() {
+            Some(step) => step.trim().parse().map_err(StatusParseErr::InvalidStep)?,
+            None => 0,
+        };
+
+        let reviews = match it.next() {
+            Some(reviews) => reviews
+                .trim()
+                .parse()
+                .map_err(StatusParseErr::InvalidReviews)?,
+            None => 0,
+        };
+
+        let lapses = match it.next() {
+            Some(lapses) => lapses
+                .trim()
+                .parse()
+                .map_err(StatusParseErr::InvalidLapses)?,
+            None => 0,
+        };
 
         let ticks = if factor != 0.0 { 1 } else { INIT_TICKS };
         Ok(Status {
@@ -158,6 +367,9 @@ impl FromStr for Status {
             timestamp,
             factor,
             ticks,
+            step,
+            reviews,
+            lapses,
         })
     }
 }
@@ -183,6 +395,46 @@ mod test_card {
         assert_eq!(c.id, 12);
         assert_eq!(c.answer, "answer");
         assert_eq!(c.cues, vec!["cue 1", "cue 2", "こんにちは世界"]);
+        assert!(c.tags.is_empty());
+
+        let c = Card::from_str("3 | answer | cue | @geo  @europe").unwrap();
+        assert_eq!(c.cues, vec!["cue"]);
+        assert_eq!(c.tags, vec!["geo", "europe"]);
+        assert_eq!(c.note, None);
+
+        let c = Card::from_str("4 | answer | cue | % a note | @geo").unwrap();
+        assert_eq!(c.cues, vec!["cue"]);
+        assert_eq!(c.note.as_deref(), Some("a note"));
+        assert_eq!(c.tags, vec!["geo"]);
+        let c = Card::from_str("5 | answer | cue | %a note").unwrap();
+        assert_eq!(c.cues, vec!["cue"]);
+        assert_eq!(c.note.as_deref(), Some("a note"));
+
+        // within the deck's width, `%` and `@` start ordinary cues.
+        let c = Card::parse_with_width("6 | answer | cue | %5", Some(2)).unwrap();
+        assert_eq!(c.cues, vec!["cue", "%5"]);
+        assert_eq!(c.note, None);
+        let c = Card::parse_with_width("7 | answer | @handle | %a note | @geo", Some(1)).unwrap();
+        assert_eq!(c.cues, vec!["@handle"]);
+        assert_eq!(c.note.as_deref(), Some("a note"));
+        assert_eq!(c.tags, vec!["geo"]);
+        assert_eq!(
+            count_cues("7 | answer | @handle | %a note | @geo", Some(1)),
+            1
+        );
+        assert_eq!(count_cues("6 | answer | cue | %5", Some(2)), 2);
+        assert_eq!(count_cues("6 | answer | cue | %5", None), 1);
+    }
+
+    #[test]
+    fn test_card_priority() {
+        let prio = |s| Card::from_str(s).unwrap().priority();
+        assert_eq!(prio("1 | a | b"), Priority::Normal);
+        assert_eq!(prio("1 | a | b | @geo"), Priority::Normal);
+        assert_eq!(prio("1 | a | b | @geo @prio:low"), Priority::Low);
+        assert_eq!(prio("1 | a | b | @prio:high"), Priority::High);
+        assert_eq!(prio("1 | a | b | @prio:urgent"), Priority::Normal);
+        assert!(Priority::High < Priority::Normal && Priority::Normal < Priority::Low);
     }
 
     #[test]
@@ -201,6 +453,34 @@ mod test_card {
             Status::from_str("1,100,a"),
             Err(StatusParseErr::InvalidFactor(_))
         ));
+        assert_eq!(Status::from_str("1,100,0.0").unwrap().step, 0);
+        assert_eq!(Status::from_str("1,100,0.0,1").unwrap().step, 1);
+        assert!(matches!(
+            Status::from_str("1,100,0.0,a"),
+            Err(StatusParseErr::InvalidStep(_))
+        ));
+        assert_eq!(Status::from_str("1,100,2.0,0,3").unwrap().reviews, 3);
+        assert!(matches!(
+            Status::from_str("1,100,2.0,0,a"),
+            Err(StatusParseErr::InvalidReviews(_))
+        ));
+        assert_eq!(Status::from_str("1,100,2.0,0,3,4").unwrap().lapses, 4);
+        assert!(matches!(
+            Status::from_str("1,100,2.0,0,3,a"),
+            Err(StatusParseErr::InvalidLapses(_))
+        ));
+        assert_eq!(
+            Status::from_str("1,100,-2.0"),
+            Err(StatusParseErr::NegativeFactor)
+        );
+        for factor in ["NaN", "inf", "-inf"] {
+            assert_eq!(
+                Status::from_str(&format!("1,100,{}", factor)),
+                Err(StatusParseErr::NonFiniteFactor)
+            );
+        }
+        assert_eq!(Status::from_str("1,100,0.001").unwrap().factor, 1.0);
+        assert!(Status::from_str("1,100,-0.0").unwrap().is_new());
 
         let c =
             Card::from_str("12 |    answer    |   cue 1   |    cue 2   |  こんにちは世界").unwrap();
@@ -215,34 +495,142 @@ mod test_card {
             "1 | ans",
             "2 | 日本語 | ελλενικη",
             "123123123 | ans | cue1 | cue2 | cue3 | cue4",
+            "4 | ans | cue | @tag1 @tag2",
+            "5 | ans | cue | %note | @tag",
         ] {
             assert_eq!(Ok(s.to_string()), Card::from_str(s).map(|c| c.to_string()))
         }
     }
 
+    #[test]
+    fn test_status_to_string() {
+        for s in ["1,100,1.00", "2,100,0.00,1,0,0", "3,100,4.00,0,7,2"] {
+            assert_eq!(
+                Ok(s.to_string()),
+                Status::from_str(s).map(|s| s.to_string())
+            )
+        }
+    }
+
     #[test]
     fn test_card_update() {
         let mut s = Status::new(1);
 
         for _ in 0..INIT_TICKS {
             assert_eq!(s.factor, 0.0);
-            s.update(true, false);
+            s.update(true, false, &Config::default(), Local::now());
         }
         assert_eq!(s.factor, 1.0);
 
         // new turn
         s.ticks = 1;
-        s.update(true, false);
+        s.update(true, false, &Config::default(), Local::now());
         assert_eq!(s.factor, 2.0);
 
         // new turn
         s.ticks = 1;
-        s.update(true, false);
+        s.update(true, false, &Config::default(), Local::now());
         assert_eq!(s.factor, 4.0);
+        assert_eq!(s.reviews, 3);
 
         // new turn
         s.ticks = 1;
-        s.update(false, false);
+        s.update(false, false, &Config::default(), Local::now());
         assert_eq!(s.factor, 2.0);
+        assert_eq!(s.lapses, 1);
+    }
+
+    #[test]
+    fn test_interval_mod() {
+        let config = Config {
+            interval_mod: 2.0,
+            ..Config::default()
+        };
+        let mut s = Status::new(1);
+        s.factor = 4.0;
+        s.ticks = 1;
+        let before = s.timestamp;
+        s.update(true, false, &config, Local::now());
+        assert_eq!(s.timestamp - before, 86400 * 16);
+    }
+
+    #[test]
+    fn test_review_ahead() {
+        let mut s = Status::new(1);
+        s.factor = 4.0;
+        s.ticks = 1;
+        s.timestamp += 2 * 86400;
+        let due = s.timestamp;
+        assert!(!s.is_due());
+        assert!(!s.is_due_within(1));
+        assert!(s.is_due_within(2));
+
+        // the interval is added to the due date, not to today.
+        s.update(true, false, &Config::default(), Local::now());
+        assert_eq!(s.timestamp, due + 86400 * 8);
+    }
+
+    #[test]
+    fn test_learning_steps() {
+        let config = Config {
+            learning_steps: vec![1, 10],
+            ..Config::default()
+        };
+        let mut s = Status::new(1);
+
+        // first step is short, so the card stays due.
+        s.update(false, false, &config, Local::now());
+        assert_eq!((s.step, s.ticks), (0, 2));
+        assert!(s.is_due());
+
+        // second step is long, so the card is put away for a while.
+        s.update(true, false, &config, Local::now());
+        assert_eq!((s.step, s.ticks), (1, 1));
+        assert!(s.is_new());
+        assert!(!s.is_due());
+        assert!(s.timestamp > Local::now().timestamp() + 9 * 60);
+
+        // wrong answer goes back to the first step.
+        s.update(false, false, &config, Local::now());
+        assert_eq!((s.step, s.ticks), (0, 2));
+
+        s.update(true, false, &config, Local::now());
+        s.update(true, false, &config, Local::now());
+        assert_eq!((s.step, s.ticks), (0, 0));
+        assert_eq!(s.factor, 1.0);
+        assert!(!s.is_new());
+    }
+
+    #[test]
+    fn test_relearn_new() {
+        let config = Config {
+            learning_steps: vec![0, 0, 0],
+            ..Config::default()
+        };
+        let mut s = Status::new(1);
+        s.update(true, false, &config, Local::now());
+        s.update(true, false, &config, Local::now());
+        assert_eq!(s.update(false, false, &config, Local::now()), 3);
+        for _ in 0..3 {
+            s.update(true, false, &config, Local::now());
+        }
+        assert_eq!(s.factor, 1.0);
+
+        let config = Config {
+            relearn_new: RelearnNew::AddOne,
+            ..config
+        };
+        let mut s = Status::new(1);
+        s.update(true, false, &config, Local::now());
+        s.update(true, false, &config, Local::now());
+        assert_eq!(s.update(false, false, &config, Local::now()), 2);
+        for _ in 0..2 {
+            s.update(true, false, &config, Local::now());
+        }
+        assert_eq!(s.factor, 1.0);
+
+        // a wrong answer on the first step cannot go further back.
+        let mut s = Status::new(1);
+        assert_eq!(s.update(false, false, &config, Local::now()), 3);
     }
 }
@@ -0,0 +1,273 @@
+use std::{path::PathBuf, sync::Arc};
+
+use crate::{
+    card::INIT_TICKS,
+    scheduler::{Doubling, Scheduler},
+};
+
+// learning steps shorter than this (in minutes) re-queue the card immediately.
+pub const REQUEUE_MINUTES: u32 = 5;
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub learning_steps: Vec<u32>,
+    pub relearn_new: RelearnNew,
+    // relative to the including deck's directory.
+    pub includes: Vec<PathBuf>,
+    // correct reviews a card needs, on top of its interval, to count as done.
+    pub min_reviews_done: usize,
+    // scales every interval computed after a review.
+    pub interval_mod: f64,
+    pub scheduler: Arc<dyn Scheduler>,
+    pub sort_log: SortLog,
+    // typed answers ignore trailing parenthesized context.
+    pub strip_parens: bool,
+    // typed answers whose items are split by this are graded item by item.
+    pub list_separator: Option<String>,
+    // fraction of a list's items needed for a typed answer to count as correct.
+    pub list_threshold: f64,
+    // decimals of the factor written to the log.
+    pub factor_precision: usize,
+    // language of each field, answer first, for --speak-lang. empty when not given.
+    pub langs: Vec<String>,
+    pub opts: DeckOpts,
+}
+
+// defaults for command line options, set with `# opt: ...` lines. flags given on
+// the command line win.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct DeckOpts {
+    pub new_cards: Option<usize>,
+    pub max_old: Option<usize>,
+    pub randomize: bool,
+}
+
+// what a wrong answer does to a new card's learning step.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum RelearnNew {
+    Reset,
+    AddOne,
+}
+
+// order of the lines written by save_log.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum SortLog {
+    Id,
+    Timestamp,
+    // the order of the cards in the deck file.
+    File,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigErr {
+    InvalidValue { key: String, value: String },
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            learning_steps: vec![0; INIT_TICKS],
+            relearn_new: RelearnNew::Reset,
+            includes: vec![],
+            min_reviews_done: 0,
+            interval_mod: 1.0,
+            scheduler: Arc::new(Doubling),
+            sort_log: SortLog::Id,
+            strip_parens: false,
+            list_separator: None,
+            list_threshold: 1.0,
+            factor_precision: 2,
+            langs: vec![],
+            opts: DeckOpts::default(),
+        }
+    }
+}
+
+impl Config {
+    // `line` is a deck line starting with `#`. lines not of the form
+    // `# key: value` with a known key are plain comments and are ignored.
+    pub fn apply_directive(&mut self, line: &str) -> Result<(), ConfigErr> {
+        let Some((key, value)) = line.trim_start_matches('#').split_once(':') else {
+            return Ok(());
+        };
+        let (key, value) = (key.trim(), value.trim());
+        let invalid = || ConfigErr::InvalidValue {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+
+        match key {
+            "learning_steps" => {
+                self.learning_steps = value
+                    .split(',')
+                    .map(|step| step.trim().parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| invalid())?;
+            }
+            "relearn_new" => {
+                self.relearn_new = match value {
+                    "reset" => RelearnNew::Reset,
+                    "add_one" => RelearnNew::AddOne,
+                    _ => return Err(invalid()),
+                };
+            }
+            "min_reviews_done" => {
+                self.min_reviews_done = value.parse().map_err(|_| invalid())?;
+            }
+            "sort_log" => {
+                self.sort_log = match value {
+                    "id" => SortLog::Id,
+                    "timestamp" => SortLog::Timestamp,
+                    "file" => SortLog::File,
+                    _ => return Err(invalid()),
+                };
+            }
+            "strip_parens" => self.strip_parens = value.parse().map_err(|_| invalid())?,
+            "list_separator" if !value.is_empty() => self.list_separator = Some(value.to_string()),
+            "list_separator" => return Err(invalid()),
+            "list_threshold" => {
+                self.list_threshold = value
+                    .parse()
+                    .ok()
+                    .filter(|t| *t > 0.0 && *t <= 1.0)
+                    .ok_or_else(invalid)?;
+            }
+            "factor_precision" => {
+                self.factor_precision = value
+                    .parse()
+                    .ok()
+                    .filter(|p| *p <= 17)
+                    .ok_or_else(invalid)?;
+            }
+            "lang" if !value.is_empty() => {
+                self.langs = value
+                    .split(',')
+                    .map(|lang| lang.trim().to_string())
+                    .collect()
+            }
+            "lang" => return Err(invalid()),
+            "opt" => match value.split_once('=') {
+                Some(("new_cards", n)) => {
+                    self.opts.new_cards = Some(n.trim().parse().map_err(|_| invalid())?)
+                }
+                Some(("max_old", n)) => {
+                    self.opts.max_old = Some(n.trim().parse().map_err(|_| invalid())?)
+                }
+                None if value == "randomize" => self.opts.randomize = true,
+                // playing is what happens anyway.
+                None if value == "play" => {}
+                _ => return Err(invalid()),
+            },
+            "include" if !value.is_empty() => self.includes.push(PathBuf::from(value)),
+            "include" => return Err(invalid()),
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+// assuming exponential forgetting, scaling intervals by ln(target) / ln(retention)
+// moves the measured retention towards the target.
+pub fn suggest_interval_mod(retention: f64, target: f64, current: f64) -> f64 {
+    let retention = retention.clamp(0.01, 0.99);
+    (current * target.ln() / retention.ln()).clamp(0.25, 4.0)
+}
+
+#[cfg(test)]
+mod test_config {
+    use super::*;
+
+    #[test]
+    fn test_apply_directive() {
+        let mut c = Config::default();
+        assert_eq!(c.learning_steps, vec![0, 0]);
+
+        assert!(c.apply_directive("# just a comment").is_ok());
+        assert!(c.apply_directive("# unknown: 123").is_ok());
+        // the scheduler can't be compared, the rest of the config can.
+        assert_eq!(format!("{:?}", c), format!("{:?}", Config::default()));
+
+        c.apply_directive("# learning_steps: 1, 10").unwrap();
+        assert_eq!(c.learning_steps, vec![1, 10]);
+
+        assert!(matches!(
+            c.apply_directive("# learning_steps: 1,a"),
+            Err(ConfigErr::InvalidValue { .. })
+        ));
+        assert!(c.apply_directive("#learning_steps:").is_err());
+
+        c.apply_directive("# relearn_new: add_one").unwrap();
+        assert_eq!(c.relearn_new, RelearnNew::AddOne);
+        assert!(c.apply_directive("# relearn_new: sometimes").is_err());
+
+        c.apply_directive("# include: a.mnemo").unwrap();
+        c.apply_directive("# include: ../b.mnemo").unwrap();
+        assert_eq!(
+            c.includes,
+            vec![PathBuf::from("a.mnemo"), PathBuf::from("../b.mnemo")]
+        );
+        assert!(c.apply_directive("# include:").is_err());
+
+        c.apply_directive("# min_reviews_done: 5").unwrap();
+        assert_eq!(c.min_reviews_done, 5);
+        assert!(c.apply_directive("# min_reviews_done: -1").is_err());
+
+        assert_eq!(c.sort_log, SortLog::Id);
+        c.apply_directive("# sort_log: timestamp").unwrap();
+        assert_eq!(c.sort_log, SortLog::Timestamp);
+        assert!(c.apply_directive("# sort_log: random").is_err());
+
+        c.apply_directive("# opt: new_cards=5").unwrap();
+        c.apply_directive("# opt: randomize").unwrap();
+        c.apply_directive("# opt: play").unwrap();
+        assert_eq!(
+            c.opts,
+            DeckOpts {
+                new_cards: Some(5),
+                max_old: None,
+                randomize: true,
+            }
+        );
+        assert!(c.apply_directive("# opt: new_cards=many").is_err());
+        assert!(c.apply_directive("# opt: audio").is_err());
+
+        c.apply_directive("# strip_parens: true").unwrap();
+        assert!(c.strip_parens);
+        assert!(c.apply_directive("# strip_parens: yes").is_err());
+
+        c.apply_directive("# list_separator: ;").unwrap();
+        assert_eq!(c.list_separator.as_deref(), Some(";"));
+        assert!(c.apply_directive("# list_separator:").is_err());
+        c.apply_directive("# list_threshold: 0.66").unwrap();
+        assert_eq!(c.list_threshold, 0.66);
+        assert!(c.apply_directive("# list_threshold: 0").is_err());
+        assert!(c.apply_directive("# list_threshold: 2").is_err());
+        c.apply_directive("# factor_precision: 6").unwrap();
+        assert_eq!(c.factor_precision, 6);
+        assert!(c.apply_directive("# factor_precision: 20").is_err());
+
+        c.apply_directive("# lang: fr, en,").unwrap();
+        assert_eq!(c.langs, vec!["fr", "en", ""]);
+        assert!(c.apply_directive("# lang:").is_err());
+    }
+
+    #[test]
+    fn test_suggest_interval_mod() {
+        // on target: keep the current modifier.
+        assert!((suggest_interval_mod(0.9, 0.9, 1.0) - 1.0).abs() < 1e-9);
+        assert!((suggest_interval_mod(0.9, 0.9, 1.5) - 1.5).abs() < 1e-9);
+
+        // remembering more than needed: longer intervals.
+        let m = suggest_interval_mod(0.95, 0.9, 1.0);
+        assert!((m - 0.9f64.ln() / 0.95f64.ln()).abs() < 1e-9);
+        assert!(m > 2.0);
+
+        // remembering too little: shorter intervals.
+        assert!(suggest_interval_mod(0.8, 0.9, 1.0) < 0.5);
+
+        // extremes are clamped.
+        assert_eq!(suggest_interval_mod(1.0, 0.9, 1.0), 4.0);
+        assert_eq!(suggest_interval_mod(0.0, 0.9, 1.0), 0.25);
+    }
+}
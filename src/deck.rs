@@ -1,21 +1,39 @@
 use std::{
     collections::{HashMap, HashSet},
     fs::File,
-    io::Write,
+    io::{BufRead, Read, Write},
     path::{Path, PathBuf},
-    process::exit,
+    process::{Child, Command, Stdio},
     str::FromStr,
+    time::{Duration, Instant},
 };
 
-use chrono::{Datelike, Local};
+use chrono::{format::StrftimeItems, DateTime, Local, NaiveDate, TimeZone};
 use colored::Colorize;
 
-use crate::card::{Card, CardParseErr, Status, StatusParseErr};
+use crate::{
+    answer,
+    card::{self, Card, CardParseErr, Priority, Status, StatusParseErr},
+    config::{Config, ConfigErr, SortLog},
+    suite::PlayOptions,
+};
+
+pub const BACKUP_DIR: &str = "/tmp/mnemo";
 
-const BACKUP_DIR: &str = "/tmp/mnemo";
+// the suffix of the log backups sessions take, for list_backups.
+pub const SESSION_LOG_SUFFIX: &str = ".session.log";
 
 const MAX_DAYS: f64 = 60.0;
 
+// cards from the n-th `# include:` get their ids shifted by (n + 1) * INCLUDE_ID_OFFSET.
+pub const INCLUDE_ID_OFFSET: usize = 1_000_000;
+
+// `+` at the grade prompt scales a card's factor by 1 + this, `-` by the inverse.
+pub const EASE_STEP: f64 = 0.2;
+
+// --auto-advance sleeps at most this long at a time.
+const WAIT_STEP: Duration = Duration::from_millis(100);
+
 #[derive(Debug)]
 pub struct Deck {
     pub path: PathBuf,
@@ -25,9 +43,16 @@ pub struct Deck {
     pub status: HashMap<usize, Status>,
     pub ids: Vec<usize>,
     pub header: Option<Card>,
+    pub config: Config,
+    // epoch at which add_cards wrote each card, from the `.added` sidecar.
+    pub added: HashMap<usize, i64>,
+    // problems that did not stop the deck from loading.
+    pub warnings: Vec<String>,
 
     fields: usize,
     highest_id: usize,
+    // set for decks not backed by a file. scheduling is not persisted.
+    pub read_only: bool,
 
     pub played: HashSet<usize>,
     pub wrong: HashSet<usize>,
@@ -38,327 +63,1870 @@ pub enum DeckErr {
     FileNotFound,
     BadStatus {
         line: usize,
+        content: String,
         err: StatusParseErr,
     },
     BadCard {
         line: usize,
+        content: String,
         err: CardParseErr,
     },
+    BadDirective {
+        line: usize,
+        content: String,
+        err: ConfigErr,
+    },
     InconsistentNumberOfFields {
         id: usize,
         line: usize,
+        content: String,
+        size: usize,
+        expected_size: usize,
+    },
+    // the id would not fit in a usize, or for added cards, below INCLUDE_ID_OFFSET.
+    IdOverflow {
+        id: usize,
+    },
+    // counts of cues, not including the answer.
+    HeaderFieldMismatch {
+        header_fields: usize,
+        card_fields: usize,
+    },
+    BadInclude {
+        path: PathBuf,
+        err: Box<DeckErr>,
+    },
+    IncludeCycle(PathBuf),
+    // an included card's shifted id is already taken, e.g. by a nested include
+    // or by the deck's own card.
+    IncludeIdCollision {
+        path: PathBuf,
+        id: usize,
+    },
+    NoDecksInDirectory,
+    // the deck file can't be written to.
+    ReadOnly,
+    IncludeFieldMismatch {
+        path: PathBuf,
         size: usize,
         expected_size: usize,
     },
 }
 
-impl Deck {
-    pub fn read_from_file(path: &Path) -> Result<Deck, DeckErr> {
-        let card_contents = std::fs::read_to_string(path).map_err(|_| DeckErr::FileNotFound)?;
+impl DeckErr {
+    // the line that could not be parsed, looking inside includes.
+    pub fn content(&self) -> Option<&str> {
+        match self {
+            DeckErr::BadStatus { content, .. }
+            | DeckErr::BadCard { content, .. }
+            | DeckErr::BadDirective { content, .. }
+            | DeckErr::InconsistentNumberOfFields { content, .. } => Some(content),
+            DeckErr::BadInclude { err, .. } => err.content(),
+            _ => None,
+        }
+    }
+}
 
-        let cards_vec = card_contents
-            .lines()
-            .enumerate()
-            .map(|(i, line)| Card::from_str(line).map_err(|err| DeckErr::BadCard { line: i, err }))
-            .collect::<Result<Vec<_>, DeckErr>>()?;
+// `<deck>.log` next to the deck, or with a log dir, named after the deck's
+// full path inside it, like the backups.
+fn log_path_of(path: &Path, log_dir: Option<&Path>) -> PathBuf {
+    match log_dir {
+        None => PathBuf::from(path.to_string_lossy().into_owned() + ".log"),
+        Some(dir) => dir.join(flat_name(path) + ".log"),
+    }
+}
 
-        let log_path = {
-            let path = path.to_string_lossy().into_owned() + ".log";
-            Path::new(&path).to_path_buf()
-        };
+// the canonical path as a single file name.
+// a deck that no longer exists is named by its directory, so its backups can
+// still be found.
+pub fn flat_name(path: &Path) -> String {
+    path.canonicalize()
+        .or_else(|err| {
+            let dir = match path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            Ok::<_, std::io::Error>(dir.canonicalize()?.join(path.file_name().ok_or(err)?))
+        })
+        .unwrap_or_else(|_| path.to_owned())
+        .to_string_lossy()
+        .trim_start_matches('/')
+        .replace('/', "_")
+}
 
-        let status = if let Ok(log_contents) = std::fs::read_to_string(&log_path) {
-            log_contents
-                .lines()
-                .enumerate()
-                .map(|(i, line)| {
-                    let status = Status::from_str(line)
-                        .map_err(|err| DeckErr::BadStatus { line: i, err })?;
-                    Ok((status.id, status))
-                })
-                .collect::<Result<_, _>>()?
-        } else {
-            HashMap::new()
-        };
+// what changed going from one deck to another, matching cards by id.
+#[derive(Debug, PartialEq)]
+pub struct DeckDiff {
+    pub added: Vec<usize>,
+    pub removed: Vec<usize>,
+    // id, old card, new card.
+    pub changed: Vec<(usize, String, String)>,
+}
 
-        let fields = if !cards_vec.is_empty() {
-            let expected_size = cards_vec.first().unwrap().cues.len();
-            if let Some(first_inconsistent_pos) = cards_vec
-                .iter()
-                .position(|card| card.cues.len() != expected_size)
-            {
-                let card = &cards_vec[first_inconsistent_pos];
-                return Err(DeckErr::InconsistentNumberOfFields {
-                    id: card.id,
-                    line: first_inconsistent_pos + 1,
-                    size: card.cues.len(),
-                    expected_size,
-                });
-            }
-            expected_size + 1
-        } else {
-            0
+impl DeckDiff {
+    pub fn write(&self, w: &mut dyn Write) -> std::io::Result<()> {
+        let ids = |ids: &[usize]| {
+            ids.iter()
+                .map(|id| format!("#{}", id))
+                .collect::<Vec<_>>()
+                .join(", ")
         };
+        writeln!(w, "added: {}", ids(&self.added))?;
+        writeln!(w, "removed: {}", ids(&self.removed))?;
+        writeln!(w, "changed: {}", self.changed.len())?;
+        for (_, old, new) in self.changed.iter() {
+            writeln!(w, "{}", format!("- {}", old).red())?;
+            writeln!(w, "{}", format!("+ {}", new).green())?;
+        }
+        Ok(())
+    }
+}
 
-        let ids = cards_vec
-            .iter()
-            .filter_map(|card| (card.id != 0).then_some(card.id))
-            .collect::<Vec<_>>();
+pub fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
 
-        let mut cards = cards_vec
-            .into_iter()
-            .map(|card| (card.id, card))
-            .collect::<HashMap<_, _>>();
+pub const FORECAST_DAYS: usize = 7;
 
-        let header = cards.remove(&0);
-        let highest_id = ids
-            .iter()
-            .max()
-            .max(status.keys().max())
-            .copied()
-            .unwrap_or(0);
+// card counts, and forecast[i] learned cards coming due i days from today.
+// overdue cards count as due today.
+#[derive(Debug, PartialEq)]
+pub struct Stats {
+    pub new: usize,
+    pub due: usize,
+    pub done: usize,
+    pub total: usize,
+    pub forecast: Vec<usize>,
+}
 
-        Ok(Deck {
-            path: path.to_owned(),
-            log_path,
+impl Stats {
+    // one row per forecast day:
+    // date,deck,new,due,done,total,forecast_day_offset,forecast_count
+    // the date is always iso, whatever --date-format says, and the deck is quoted
+    // when it has to be, so that the columns stay apart.
+    pub fn write_csv(&self, w: &mut dyn Write, date: NaiveDate, deck: &str) -> std::io::Result<()> {
+        let deck = csv_field(deck);
+        for (offset, count) in self.forecast.iter().enumerate() {
+            writeln!(
+                w,
+                "{},{},{},{},{},{},{},{}",
+                date, deck, self.new, self.due, self.done, self.total, offset, count
+            )?;
+        }
+        Ok(())
+    }
+}
 
-            cards,
-            status,
-            ids,
-            header,
+// quoted, with quotes doubled, if it holds a comma, quote or line break.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
 
-            fields,
-            highest_id,
+pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
 
-            played: HashSet::new(),
-            wrong: HashSet::new(),
-        })
+// checks that `format` is a strftime format that can be used for plain dates.
+pub fn validate_date_format(format: &str) -> bool {
+    use std::fmt::Write;
+    StrftimeItems::new(format).parse().is_ok()
+        && write!(String::new(), "{}", NaiveDate::MIN.format(format)).is_ok()
+}
+
+// `format` must have passed validate_date_format.
+pub fn format_date(date: NaiveDate, format: &str) -> String {
+    date.format(format).to_string()
+}
+
+// for Deck::possible_swaps. `ratio,words`, e.g. `4,4`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SwapThresholds {
+    pub ratio: f64,
+    pub words: usize,
+}
+
+impl Default for SwapThresholds {
+    fn default() -> Self {
+        SwapThresholds {
+            ratio: 4.0,
+            words: 4,
+        }
     }
+}
 
-    // returns false on quit
-    pub fn play_card(&mut self, id: usize, conceal_number: bool) -> bool {
-        println!(
-            "{}::#{}",
-            self.path.to_string_lossy().green(),
-            if conceal_number {
-                "?".to_string()
-            } else {
-                id.to_string()
-            }
+impl FromStr for SwapThresholds {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ratio, words) = s.split_once(',').ok_or(())?;
+        let ratio = ratio.trim().parse::<f64>().map_err(|_| ())?;
+        let words = words.trim().parse().map_err(|_| ())?;
+        if !(ratio >= 1.0 && ratio.is_finite()) {
+            return Err(());
+        }
+        Ok(SwapThresholds { ratio, words })
+    }
+}
+
+// copies `path` to BACKUP_DIR, named after the deck at `deck_path`.
+// backups are named after the deck, with `suffix` telling its files apart.
+// returns where the backup went, if there was anything to back up.
+pub fn backup_file(path: &Path, deck_path: &Path, suffix: &str) -> Option<PathBuf> {
+    if !path.exists() {
+        return None;
+    }
+    eprintln!("backing up {}.", path.to_string_lossy());
+    let backup_dir = Path::new(BACKUP_DIR);
+    let backup_file = backup_dir.join(Path::new(
+        &(format!(
+            "{}{}.{}",
+            flat_name(deck_path),
+            suffix,
+            Local::now().timestamp()
+        )),
+    ));
+    let copied =
+        std::fs::create_dir_all(BACKUP_DIR).and_then(|_| std::fs::copy(path, &backup_file));
+    if let Err(err) = copied {
+        eprintln!(
+            "warning: could not back up {}: {}",
+            path.to_string_lossy(),
+            err
         );
-        for (i, cue) in self.cards[&id].cues.iter().enumerate() {
-            if !cue.is_empty() {
-                let header = &self
-                    .header
-                    .as_ref()
-                    .map(|h| h.cues[i].clone())
-                    .filter(|s| !s.is_empty())
-                    .unwrap_or_else(|| "cue".to_string());
-                println!("{}: {}", header.blue(), cue);
+        return None;
+    }
+    Some(backup_file)
+}
+
+// appends empty cues to cards with fewer fields than the header, or without one,
+// than the widest card. other lines are kept as they are. also returns how many
+// cards were padded.
+pub fn pad_fields(contents: &str) -> (String, usize) {
+    let cards = contents
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| Card::from_str(line).ok())
+        .collect::<Vec<_>>();
+    let fields = match cards.iter().find(|card| card.id == 0) {
+        Some(header) => header.cues.len(),
+        None => cards.iter().map(|card| card.cues.len()).max().unwrap_or(0),
+    };
+    let mut padded = 0;
+    let mut ret = String::new();
+    for line in contents.lines() {
+        match Card::from_str(line) {
+            Ok(mut card) if !line.starts_with('#') && card.cues.len() < fields => {
+                card.cues.resize(fields, String::new());
+                ret += &card.to_string();
+                padded += 1;
             }
+            _ => ret += line,
         }
+        ret.push('\n');
+    }
+    (ret, padded)
+}
 
-        let mut ans = String::new();
-
-        print!("reveal... ");
-        std::io::stdout().flush().unwrap();
-        match std::io::stdin().read_line(&mut ans) {
-            Ok(_) => {}
-            Err(_) => ans.clear(),
+// rewrites every card line as `id | answer | cue | ...`, with the columns padded
+// to line up if `align`. comments, directives and blank lines are kept as they
+// are. returns the new contents and how many lines changed.
+pub fn reformat(contents: &str, align: bool) -> (String, usize) {
+    let cards = contents
+        .lines()
+        .map(|line| {
+            (!line.starts_with('#'))
+                .then(|| Card::from_str(line).ok())
+                .flatten()
+        })
+        .collect::<Vec<_>>();
+    let mut widths = vec![];
+    if align {
+        for card in cards.iter().flatten() {
+            let fields = std::iter::once(&card.answer).chain(card.cues.iter());
+            for (i, field) in fields.enumerate() {
+                if widths.len() <= i {
+                    widths.push(0);
+                }
+                widths[i] = widths[i].max(answer::display_width(field));
+            }
         }
-        if ans.trim() == "q" {
-            return false;
+    }
+    let mut changed = 0;
+    let mut ret = String::new();
+    for (line, card) in contents.lines().zip(cards) {
+        let formatted = match card {
+            Some(card) if align => {
+                let pad = |field: &str, width: usize| {
+                    format!(
+                        "{}{}",
+                        field,
+                        " ".repeat(width - answer::display_width(field))
+                    )
+                };
+                let mut aligned = Card {
+                    answer: pad(&card.answer, widths[0]),
+                    cues: card
+                        .cues
+                        .iter()
+                        .zip(widths[1..].iter())
+                        .map(|(cue, &width)| pad(cue, width))
+                        .collect(),
+                    ..card
+                };
+                // no padding after the last column.
+                if aligned.note.is_none() && aligned.tags.is_empty() {
+                    if let Some(last) = aligned.cues.last_mut() {
+                        *last = last.trim_end().to_string();
+                    }
+                }
+                aligned.to_string().trim_end().to_string()
+            }
+            Some(card) => card.to_string().trim_end().to_string(),
+            None => line.to_string(),
+        };
+        if formatted != line {
+            changed += 1;
         }
-        ans.clear();
+        ret += &formatted;
+        ret.push('\n');
+    }
+    (ret, changed)
+}
 
-        let header = &self
-            .header
-            .as_ref()
-            .map(|h| h.answer.clone())
-            .filter(|s| !s.is_empty())
-            .unwrap_or_else(|| "answer".to_string());
-        println!("{}: {}", header.blue(), self.cards[&id].answer);
-
-        while !["y", "n"].contains(&ans.as_str()) {
-            ans.clear();
-            print!("correct? [y/n] ");
-            std::io::stdout().flush().unwrap();
-            match std::io::stdin().read_line(&mut ans) {
-                Ok(_) => ans = ans.to_lowercase().trim().to_string(),
-                Err(_) => ans.clear(),
+// numbers the cards of a deck file from 1 in file order. only the ids change,
+// so comments, directives and spacing are kept. also returns the new id of each
+// old one.
+pub fn renumber(contents: &str) -> (String, HashMap<usize, usize>) {
+    let mut new_ids = HashMap::new();
+    let mut ret = String::new();
+    for line in contents.lines() {
+        match Card::from_str(line) {
+            Ok(card) if !line.starts_with('#') && card.id != 0 => {
+                let new_id = new_ids.len() + 1;
+                new_ids.insert(card.id, new_id);
+                let start = line.len() - line.trim_start().len();
+                let end = line[start..]
+                    .find(|c: char| !c.is_ascii_digit())
+                    .map_or(line.len(), |i| start + i);
+                ret += &format!("{}{}{}", &line[..start], new_id, &line[end..]);
             }
+            _ => ret += line,
         }
+        ret.push('\n');
+    }
+    (ret, new_ids)
+}
+
+pub fn parse_log(contents: &str) -> Result<HashMap<usize, Status>, DeckErr> {
+    contents
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let status = Status::from_str(line).map_err(|err| DeckErr::BadStatus {
+                line: i + 1,
+                content: line.to_string(),
+                err,
+            })?;
+            Ok((status.id, status))
+        })
+        .collect()
+}
 
-        let correct = ans == "y";
+// replaces invalid UTF-8 with U+FFFD. also returns the first line (1-based)
+// that contained invalid bytes, if any.
+fn decode_lossy(bytes: &[u8]) -> (String, Option<usize>) {
+    let bad_line = bytes
+        .split(|&b| b == b'\n')
+        .position(|line| std::str::from_utf8(line).is_err())
+        .map(|i| i + 1);
+    (String::from_utf8_lossy(bytes).into_owned(), bad_line)
+}
 
-        let status = self.status.entry(id).or_insert_with(|| Status::new(id));
-        let ticks = status.update(correct, true);
+// backups of the deck at `deck_path` with the unix time they were taken, newest
+// first. `suffix` is as given to backup_file, e.g. ".log" for its log.
+pub fn list_backups(deck_path: &Path, suffix: &str) -> Vec<(PathBuf, i64)> {
+    let prefix = flat_name(deck_path) + suffix + ".";
+    let Ok(entries) = std::fs::read_dir(BACKUP_DIR) else {
+        return vec![];
+    };
+    let mut backups = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_str()?;
+            // other suffixes come before the timestamp, and don't parse.
+            let timestamp = name.strip_prefix(&prefix)?.parse().ok()?;
+            Some((path, timestamp))
+        })
+        .collect::<Vec<_>>();
+    backups.sort_by_key(|&(_, timestamp)| std::cmp::Reverse(timestamp));
+    backups
+}
 
-        print!(
-            "{}. ",
-            if correct {
-                "ok".green()
-            } else {
-                "failed".red()
+// puts a backup from list_backups back in place of the deck. the log is left
+// alone, so the cards come back with their current scheduling. a deck that is
+// still there is backed up before it is replaced.
+pub fn restore_backup(backup: &Path, deck_path: &Path) -> std::io::Result<()> {
+    // read first: a backup taken in the same second would overwrite it.
+    let contents = std::fs::read(backup)?;
+    backup_file(deck_path, deck_path, "");
+    std::fs::write(deck_path, contents)
+}
+
+// tries opening an existing file for writing, without changing it. catches
+// read-only mounts and directories too, which permission bits don't tell.
+fn write_error(path: &Path) -> Option<std::io::Error> {
+    if !path.exists() {
+        return None;
+    }
+    File::options().append(true).open(path).err()
+}
+
+fn append_to(path: &Path) -> std::io::Result<File> {
+    File::options().append(true).create(true).open(path)
+}
+
+fn added_path_of(path: &Path) -> PathBuf {
+    PathBuf::from(path.to_string_lossy().into_owned() + ".added")
+}
+
+// one `id,timestamp,correct[,confident]` line per review, confident only with
+// --confidence.
+fn history_path_of(path: &Path) -> PathBuf {
+    PathBuf::from(path.to_string_lossy().into_owned() + ".history")
+}
+
+// cards for add_cards from `input` until it ends. on a terminal, says so first
+// instead of waiting silently.
+pub fn read_new_cards(input: &mut dyn Read, out: &mut dyn Write, is_terminal: bool) -> String {
+    if is_terminal {
+        writeln!(out, "enter cards, one per line, Ctrl-D to finish:").unwrap();
+        out.flush().unwrap();
+    }
+    let mut cards = String::new();
+    input.read_to_string(&mut cards).unwrap();
+    cards
+}
+
+// cuts the cues short on reveal. waiting reaps the process, which would otherwise
+// linger as a zombie for the rest of the session.
+fn stop_speaking(speaker: Option<Child>) {
+    if let Some(mut child) = speaker {
+        child.kill().ok();
+        child.wait().ok();
+    }
+}
+
+// sleeps in short steps until the clock says `delay` has passed, so a clock that
+// jumps ahead, e.g. after a suspend, ends the wait. returns the time waited.
+pub fn wait(
+    delay: Duration,
+    clock: &mut dyn FnMut() -> Duration,
+    sleep: &mut dyn FnMut(Duration),
+) -> Duration {
+    let start = clock();
+    loop {
+        let waited = clock().saturating_sub(start);
+        if waited >= delay {
+            return waited;
+        }
+        sleep((delay - waited).min(WAIT_STEP));
+    }
+}
+
+impl Deck {
+    // with a `log_dir`, the log is read from and saved there instead of next to the deck.
+    pub fn read_from_file(path: &Path, log_dir: Option<&Path>) -> Result<Deck, DeckErr> {
+        Deck::read_with_includes(path, log_dir, &mut vec![])
+    }
+
+    // for counting due/new/done without the cost of parsing every card: reads the
+    // directives, the id column and the log, and leaves `cards` empty. fields are
+    // only counted, so a deck that would not load still fails here. new cards are
+    // not ordered by priority. decks with includes are read in full.
+    pub fn read_status_only(path: &Path, log_dir: Option<&Path>) -> Result<Deck, DeckErr> {
+        let bytes = std::fs::read(path).map_err(|_| DeckErr::FileNotFound)?;
+        let contents = String::from_utf8_lossy(&bytes);
+
+        let mut config = Config::default();
+        let mut ids = vec![];
+        let mut expected_size = None;
+        for (i, line) in contents.lines().enumerate() {
+            if line.starts_with('#') {
+                config
+                    .apply_directive(line)
+                    .map_err(|err| DeckErr::BadDirective {
+                        line: i + 1,
+                        content: line.to_string(),
+                        err,
+                    })?;
+                continue;
             }
-        );
-        if ticks == 0 {
-            if self.status[&id].factor < MAX_DAYS {
-                println!("due in {} days.", self.status[&id].days_left());
-            } else {
-                println!("card is {}!", "done".green());
+            let id = line
+                .split('|')
+                .next()
+                .unwrap_or("")
+                .trim()
+                .parse()
+                .map_err(|err| DeckErr::BadCard {
+                    line: i + 1,
+                    content: line.to_string(),
+                    err: CardParseErr::InvalidId(err),
+                })?;
+            let size = card::count_cues(line, expected_size);
+            if *expected_size.get_or_insert(size) != size {
+                return Err(DeckErr::InconsistentNumberOfFields {
+                    id,
+                    line: i + 1,
+                    content: line.to_string(),
+                    size,
+                    expected_size: expected_size.unwrap(),
+                });
             }
-            self.played.insert(id);
-            if !correct {
-                self.wrong.insert(id);
+            if id != 0 {
+                ids.push(id);
             }
-            self.save_log();
-        } else {
-            println!("{} ticks left.", ticks);
+        }
+        if !config.includes.is_empty() {
+            return Deck::read_from_file(path, log_dir);
         }
 
-        println!();
-        true
+        let log_path = log_path_of(path, log_dir);
+        let status = if let Ok(log_contents) = std::fs::read_to_string(&log_path) {
+            parse_log(&log_contents)?
+        } else {
+            HashMap::new()
+        };
+
+        Ok(Deck {
+            log_path,
+            status,
+            ids,
+            config,
+            read_only: true,
+            ..Deck::new(path, 0)
+        })
     }
 
-    pub fn get_due(&self) -> Vec<usize> {
-        let mut old = self
-            .cards
-            .keys()
-            .copied()
-            .filter(|id| {
-                self.status
-                    .get(id)
-                    .map(|status| status.is_due() && !status.is_new() && status.factor < MAX_DAYS)
-                    .unwrap_or(false)
-            })
-            .collect::<Vec<_>>();
-        old.sort_by_key(|id| self.status[id].timestamp);
-        old
+    // the deck has no log, so every card is new, and nothing is ever written back.
+    pub fn read_from_reader(reader: &mut dyn Read) -> Result<Deck, DeckErr> {
+        let mut bytes = vec![];
+        reader
+            .read_to_end(&mut bytes)
+            .map_err(|_| DeckErr::FileNotFound)?;
+        let (card_contents, bad_line) = decode_lossy(&bytes);
+        let mut deck = Deck::parse(Path::new("<stdin>"), &card_contents, HashMap::new())?;
+        deck.warn_invalid_utf8(bad_line);
+        deck.warn_suspicious();
+        deck.read_only = true;
+        deck.resolve_includes(Path::new(""), None, &mut vec![])?;
+        Ok(deck)
     }
 
-    pub fn get_done(&self) -> Vec<usize> {
-        let mut done = self
-            .cards
-            .keys()
-            .copied()
-            .filter(|id| {
-                self.status
-                    .get(id)
-                    .map(|status| status.factor >= MAX_DAYS)
-                    .unwrap_or(false)
-            })
-            .collect::<Vec<_>>();
-        done.sort_by_key(|id| self.status[id].timestamp);
-        done
+    // `stack` holds the canonical paths of the decks currently being read,
+    // to detect include cycles.
+    fn read_with_includes(
+        path: &Path,
+        log_dir: Option<&Path>,
+        stack: &mut Vec<PathBuf>,
+    ) -> Result<Deck, DeckErr> {
+        let canonical = path.canonicalize().map_err(|_| DeckErr::FileNotFound)?;
+        if stack.contains(&canonical) {
+            return Err(DeckErr::IncludeCycle(path.to_owned()));
+        }
+
+        let bytes = std::fs::read(path).map_err(|_| DeckErr::FileNotFound)?;
+        let (card_contents, bad_line) = decode_lossy(&bytes);
+
+        let log_path = log_path_of(path, log_dir);
+        let status = if let Ok(log_contents) = std::fs::read_to_string(&log_path) {
+            parse_log(&log_contents)?
+        } else {
+            HashMap::new()
+        };
+
+        let mut deck = Deck::parse(path, &card_contents, status)?;
+        if let Some(err) = write_error(&log_path) {
+            deck.read_only = true;
+            deck.warnings.push(format!(
+                "{} is read-only ({}), progress will not be saved. --log-dir keeps logs elsewhere",
+                log_path.to_string_lossy(),
+                err
+            ));
+        }
+        deck.log_path = log_path;
+        deck.warn_invalid_utf8(bad_line);
+        deck.warn_suspicious();
+        if let Ok(added_contents) = std::fs::read_to_string(added_path_of(path)) {
+            deck.added = added_contents
+                .lines()
+                .filter_map(|line| {
+                    let (id, timestamp) = line.split_once(',')?;
+                    Some((id.trim().parse().ok()?, timestamp.trim().parse().ok()?))
+                })
+                .collect();
+        }
+
+        stack.push(canonical);
+        let dir = path.parent().unwrap_or_else(|| Path::new("")).to_owned();
+        deck.resolve_includes(&dir, log_dir, stack)?;
+        stack.pop();
+
+        Ok(deck)
     }
 
-    pub fn get_new(&self) -> Vec<usize> {
-        let mut new = self
-            .cards
-            .keys()
-            .copied()
-            .filter(|id| {
-                self.status
-                    .get(id)
-                    .map(|status| status.is_new())
-                    .unwrap_or(true)
-            })
-            .collect::<Vec<_>>();
-        new.sort();
-        new
+    fn parse(
+        path: &Path,
+        card_contents: &str,
+        status: HashMap<usize, Status>,
+    ) -> Result<Deck, DeckErr> {
+        let mut config = Config::default();
+        for (i, line) in card_contents.lines().enumerate() {
+            if line.starts_with('#') {
+                config
+                    .apply_directive(line)
+                    .map_err(|err| DeckErr::BadDirective {
+                        line: i + 1,
+                        content: line.to_string(),
+                        err,
+                    })?;
+            }
+        }
+
+        // the first card sets the width, past which fields are notes and tags.
+        let mut cards_vec = vec![];
+        let mut width = None;
+        for (i, line) in card_contents.lines().enumerate() {
+            if line.starts_with('#') {
+                continue;
+            }
+            let card = Card::parse_with_width(line, width).map_err(|err| DeckErr::BadCard {
+                line: i + 1,
+                content: line.to_string(),
+                err,
+            })?;
+            width.get_or_insert(card.cues.len());
+            cards_vec.push((i + 1, line, card));
+        }
+
+        let mut deck = Deck::new(path, 0);
+        deck.config = config;
+        for (line, content, card) in cards_vec {
+            deck.push_card(card, line, content)?;
+        }
+        for (id, status) in status {
+            deck.set_status(id, status);
+        }
+        Ok(deck)
     }
 
-    pub fn backup_deck(&self) {
-        self.backup_file(&self.path);
+    // an empty deck. `fields` counts the answer and the cues, or is 0 to take it
+    // from the first card pushed.
+    pub fn new(path: &Path, fields: usize) -> Deck {
+        Deck {
+            path: path.to_owned(),
+            log_path: log_path_of(path, None),
+
+            cards: HashMap::new(),
+            status: HashMap::new(),
+            ids: vec![],
+            header: None,
+            config: Config::default(),
+            added: HashMap::new(),
+            warnings: vec![],
+
+            fields,
+            highest_id: 0,
+
+            read_only: false,
+
+            played: HashSet::new(),
+            wrong: HashSet::new(),
+        }
     }
 
-    pub fn backup_log(&self) {
-        self.backup_file(&self.log_path);
+    // a card with id 0 becomes the header. `line` (1-based) and `content` are
+    // where it was read from, for errors.
+    pub fn push_card(&mut self, card: Card, line: usize, content: &str) -> Result<(), DeckErr> {
+        if self.fields == 0 {
+            self.fields = card.cues.len() + 1;
+        } else if card.cues.len() + 1 != self.fields {
+            // the header labels the cues, so it is the odd one out when it disagrees
+            // with the cards, wherever it is.
+            if card.id == 0 || (self.ids.is_empty() && self.header.is_some()) {
+                let (header, card) = match &self.header {
+                    Some(header) => (header.cues.len(), card.cues.len()),
+                    None => (card.cues.len(), self.fields - 1),
+                };
+                return Err(DeckErr::HeaderFieldMismatch {
+                    header_fields: header,
+                    card_fields: card,
+                });
+            }
+            return Err(DeckErr::InconsistentNumberOfFields {
+                id: card.id,
+                line,
+                content: content.to_string(),
+                size: card.cues.len(),
+                expected_size: self.fields - 1,
+            });
+        }
+        if card.id == 0 {
+            self.header = Some(card);
+            return Ok(());
+        }
+        if card.id < INCLUDE_ID_OFFSET {
+            self.highest_id = self.highest_id.max(card.id);
+        }
+        self.ids.push(card.id);
+        self.cards.insert(card.id, card);
+        Ok(())
     }
 
-    fn backup_file(&self, path: &Path) {
-        if !path.exists() {
-            return;
+    pub fn set_status(&mut self, id: usize, status: Status) {
+        if id < INCLUDE_ID_OFFSET {
+            self.highest_id = self.highest_id.max(id);
         }
-        eprintln!("backing up {}.", path.to_string_lossy());
-        let backup_dir = Path::new(BACKUP_DIR);
-        let backup_file = backup_dir.join(Path::new(
-            &(format!(
-                "{}.{}",
-                self.path
-                    .canonicalize()
-                    .unwrap()
-                    .to_string_lossy()
-                    .trim_start_matches('/')
-                    .replace('/', "_"),
-                Local::now().timestamp()
-            )),
-        ));
-        std::fs::create_dir_all(BACKUP_DIR).expect("could not create backup directory");
-        std::fs::copy(path, backup_file).expect("backup failed");
+        self.status.insert(id, Status { id, ..status });
     }
 
-    pub fn add_cards(&self, cards: &str) {
-        self.backup_deck();
-        let mut f = std::fs::File::options()
-            .append(true)
-            .create(true)
-            .open(&self.path)
-            .unwrap_or_else(|_| panic!("could not open {}.", self.path.to_string_lossy()));
-        for (i, card) in cards
-            .lines()
-            .map(|s| s.trim())
-            .filter(|s| !s.is_empty() && !s.starts_with('#'))
-            .enumerate()
-        {
-            if card.bytes().filter(|&c| c == b'|').count() != self.fields - 1 {
-                eprintln!("bad card format at line {}", i + 1);
-                return;
-            }
-            f.write_all(format!("{} | {}\n", i + self.highest_id + 1, card).as_bytes())
-                .expect("could not write to file.");
+    fn warn_invalid_utf8(&mut self, bad_line: Option<usize>) {
+        if let Some(line) = bad_line {
+            self.warnings.push(format!(
+                "invalid UTF-8 replaced with \u{FFFD}, first at line {}",
+                line
+            ));
         }
     }
 
-    pub fn dump(&self) {
-        for id in self.ids.iter() {
-            let card = &self.cards[id];
-            let status = self
-                .status
-                .get(id)
-                .copied()
-                .unwrap_or_else(|| Status::new(*id));
-            let due = status.due_date();
-            std::io::stdout()
-                .write_all(
-                    format!(
-                        "{},{},{}-{:02}-{:02},{:.2}\n",
-                        card.id,
-                        card.answer,
-                        due.year(),
-                        due.month(),
-                        due.day(),
-                        status.factor,
-                    )
-                    .as_bytes(),
-                )
-                .unwrap_or_else(|_| exit(0)); // stupid broken pipe error
+    fn warn_suspicious(&mut self) {
+        let ids = self.suspicious_cards();
+        if !ids.is_empty() {
+            self.warnings.push(format!(
+                "answer repeated in a cue, card{} {}",
+                if ids.len() > 1 { "s" } else { "" },
+                ids.iter()
+                    .map(|id| format!("#{}", id))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
         }
     }
 
-    pub fn inspect(&self) {
-        let new = self.get_new().len();
+    // cards in file order, with their status once they have been played. the header
+    // is not a card, and decks read with read_status_only have no cards.
+    pub fn iter_cards(&self) -> impl Iterator<Item = (&Card, Option<&Status>)> {
+        self.ids
+            .iter()
+            .filter_map(|id| Some((self.cards.get(id)?, self.status.get(id))))
+    }
+
+    // cards whose answer, ignoring case and surrounding whitespace, is also one of
+    // their cues. probably authoring mistakes, since they answer themselves.
+    pub fn suspicious_cards(&self) -> Vec<usize> {
+        let normalize = |s: &str| s.trim().to_lowercase();
+        self.iter_cards()
+            .filter(|(card, _)| {
+                let answer = normalize(&card.answer);
+                card.cues.iter().any(|cue| normalize(cue) == answer)
+            })
+            .map(|(card, _)| card.id)
+            .collect()
+    }
+
+    // cards that may have their answer in a cue: a cue of `words` words or more
+    // that is at least `ratio` times as long as the answer, or any longer cue
+    // against a one letter answer. only hints, since some decks are made that way.
+    pub fn possible_swaps(&self, thresholds: SwapThresholds) -> Vec<usize> {
+        self.iter_cards()
+            .filter(|(card, _)| {
+                let answer = card.answer.trim().chars().count();
+                card.cues.iter().any(|cue| {
+                    let cue_len = cue.trim().chars().count();
+                    let words = cue.split_whitespace().count();
+                    let phrase = cue_len as f64 >= thresholds.ratio * answer as f64
+                        && words >= thresholds.words;
+                    phrase || (answer == 1 && cue_len > 1)
+                })
+            })
+            .map(|(card, _)| card.id)
+            .collect()
+    }
+
+    fn resolve_includes(
+        &mut self,
+        dir: &Path,
+        log_dir: Option<&Path>,
+        stack: &mut Vec<PathBuf>,
+    ) -> Result<(), DeckErr> {
+        for (n, include) in self.config.includes.clone().iter().enumerate() {
+            let include_path = dir.join(include);
+            let base = match Deck::read_with_includes(&include_path, log_dir, stack) {
+                Ok(base) => base,
+                Err(err @ DeckErr::IncludeCycle(_)) => return Err(err),
+                Err(err) => {
+                    return Err(DeckErr::BadInclude {
+                        path: include_path,
+                        err: Box::new(err),
+                    })
+                }
+            };
+            self.merge_include(base, (n + 1) * INCLUDE_ID_OFFSET, include_path)?;
+        }
+        Ok(())
+    }
+
+    // statuses from the included deck's log are only used until this deck's
+    // own log has an entry for the shifted id.
+    fn merge_include(&mut self, base: Deck, offset: usize, path: PathBuf) -> Result<(), DeckErr> {
+        if self.fields != 0 && base.fields != 0 && self.fields != base.fields {
+            return Err(DeckErr::IncludeFieldMismatch {
+                path,
+                size: base.fields - 1,
+                expected_size: self.fields - 1,
+            });
+        }
+        if self.fields == 0 {
+            self.fields = base.fields;
+        }
+        if self.header.is_none() {
+            self.header = base.header;
+        }
+
+        let mut base_cards = base.cards;
+        for id in base.ids {
+            let mut card = base_cards.remove(&id).unwrap();
+            card.id = card
+                .id
+                .checked_add(offset)
+                .ok_or(DeckErr::IdOverflow { id })?;
+            if self.cards.contains_key(&card.id) {
+                return Err(DeckErr::IncludeIdCollision { path, id: card.id });
+            }
+            if let Some(status) = base.status.get(&id) {
+                self.status.entry(card.id).or_insert(Status {
+                    id: card.id,
+                    ..*status
+                });
+            }
+            self.ids.push(card.id);
+            self.cards.insert(card.id, card);
+        }
+        Ok(())
+    }
+
+    // the non-empty cues in `lang`, going by the `# lang:` directive. without one,
+    // every cue is taken to be in `lang`.
+    pub fn cues_to_speak(&self, id: usize, lang: &str) -> Vec<&str> {
+        let langs = &self.config.langs;
+        self.cards[&id]
+            .cues
+            .iter()
+            .enumerate()
+            .filter(|(i, cue)| {
+                !cue.is_empty() && (langs.is_empty() || langs.get(i + 1).is_some_and(|l| l == lang))
+            })
+            .map(|(_, cue)| cue.as_str())
+            .collect()
+    }
+
+    // returns false on quit. `position` is the card's (number, total) in the
+    // session, shown instead of the id with --conceal-show-progress.
+    pub fn play_card(
+        &mut self,
+        id: usize,
+        position: Option<(usize, usize)>,
+        opts: &PlayOptions,
+        input: &mut dyn BufRead,
+        out: &mut dyn Write,
+    ) -> bool {
+        if !opts.quiet {
+            let banner = match position.filter(|_| opts.conceal_progress) {
+                Some((n, total)) => format!("card {} of {}", n, total),
+                None if opts.conceal_number => "#?".to_string(),
+                None => format!("#{}", id),
+            };
+            writeln!(out, "{}::{}", self.path.to_string_lossy().green(), banner).unwrap();
+        }
+        for (i, cue) in self.cards[&id].cues.iter().enumerate() {
+            if !cue.is_empty() || opts.show_empty_cues {
+                let header = &self
+                    .header
+                    .as_ref()
+                    .map(|h| h.cues[i].clone())
+                    .filter(|s| !s.is_empty())
+                    .unwrap_or_else(|| "cue".to_string());
+                writeln!(out, "{}: {}", header.blue(), cue).unwrap();
+            }
+        }
+
+        // best effort: a missing audio command just means no audio.
+        let speaker = opts.speak_lang.as_ref().and_then(|lang| {
+            let text = self.cues_to_speak(id, lang).join(". ");
+            if text.is_empty() {
+                return None;
+            }
+            let argv = opts.audio.argv(lang, &text).ok()?;
+            Command::new(&argv[0])
+                .args(&argv[1..])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .ok()
+        });
+
+        let mut ans = String::new();
+
+        let header = &self
+            .header
+            .as_ref()
+            .map(|h| h.answer.clone())
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| "answer".to_string());
+
+        // preview: show everything at once, no grading.
+        if opts.preview {
+            writeln!(out, "{}: {}", header.blue(), self.cards[&id].answer).unwrap();
+            write!(out, "next... ").unwrap();
+            out.flush().unwrap();
+            input.read_line(&mut ans).ok();
+            stop_speaking(speaker);
+            writeln!(out).unwrap();
+            return ans.trim() != "q";
+        }
+
+        if opts.scaffold {
+            let scaffold = answer::scaffold(&self.cards[&id].answer);
+            writeln!(out, "{}: {}", "hint".blue(), scaffold).unwrap();
+        }
+
+        if let Some(secs) = opts.auto_advance.filter(|_| !opts.typed) {
+            write!(out, "reveal in {}s... ", secs).unwrap();
+            out.flush().unwrap();
+            let start = Instant::now();
+            wait(
+                Duration::from_secs_f64(secs),
+                &mut || start.elapsed(),
+                &mut std::thread::sleep,
+            );
+            writeln!(out).unwrap();
+        } else {
+            write!(
+                out,
+                "{}",
+                // not `answer: `, which the header labels the expected answer with.
+                if opts.typed {
+                    "your answer: "
+                } else {
+                    "reveal... "
+                }
+            )
+            .unwrap();
+            out.flush().unwrap();
+            match input.read_line(&mut ans) {
+                Ok(_) => {}
+                Err(_) => ans.clear(),
+            }
+        }
+        stop_speaking(speaker);
+        if ans.trim() == "q" {
+            return false;
+        }
+        // x at reveal: known to be wrong, no need to ask.
+        let given_up = !opts.typed && ans.trim() == "x";
+        let expected = &self.cards[&id].answer;
+        let strip = self.config.strip_parens;
+        // (found, missed) for list answers.
+        let items = opts
+            .typed
+            .then_some(self.config.list_separator.as_ref())
+            .flatten()
+            .map(|sep| answer::match_items(&ans, expected, sep, strip));
+        let typed_correct = opts.typed.then(|| match &items {
+            Some((found, missed)) => {
+                let total = found.len() + missed.len();
+                found.len() as f64 >= self.config.list_threshold * total as f64 - 1e-9
+            }
+            None => answer::matches(&ans, expected, strip),
+        });
+        ans.clear();
+
+        writeln!(out, "{}: {}", header.blue(), expected).unwrap();
+        if let Some((found, missed)) = items {
+            writeln!(out, "{}/{} items.", found.len(), found.len() + missed.len()).unwrap();
+            if !missed.is_empty() {
+                writeln!(out, "missed: {}", missed.join(", ")).unwrap();
+            }
+        }
+
+        let correct = if given_up {
+            false
+        } else if let Some(correct) = typed_correct {
+            correct
+        } else {
+            loop {
+                ans.clear();
+                write!(out, "correct? {} ", opts.keys.prompt()).unwrap();
+                out.flush().unwrap();
+                match input.read_line(&mut ans) {
+                    // eof: quit instead of asking forever.
+                    Ok(0) => return false,
+                    Ok(_) => {
+                        if let Some(correct) = opts.keys.grade(&ans) {
+                            break correct;
+                        }
+                        // `+` and `-` nudge the factor of a learned card before grading.
+                        let bump = 1.0 + opts.ease_step.unwrap_or(EASE_STEP);
+                        let factor = match ans.trim() {
+                            "+" => bump,
+                            "-" => 1.0 / bump,
+                            _ => continue,
+                        };
+                        match self.status.get_mut(&id).filter(|status| !status.is_new()) {
+                            Some(status) => {
+                                status.factor = (status.factor * factor).max(1.0);
+                                writeln!(out, "factor now {:.2}.", status.factor).unwrap();
+                            }
+                            None => writeln!(out, "new cards have no factor yet.").unwrap(),
+                        }
+                    }
+                    Err(_) => {}
+                }
+            }
+        };
+
+        let confident = if opts.confidence {
+            Some(loop {
+                ans.clear();
+                write!(out, "confident? {} ", opts.keys.prompt()).unwrap();
+                out.flush().unwrap();
+                match input.read_line(&mut ans) {
+                    Ok(0) => return false,
+                    Ok(_) => {
+                        if let Some(confident) = opts.keys.grade(&ans) {
+                            break confident;
+                        }
+                    }
+                    Err(_) => {}
+                }
+            })
+        } else {
+            None
+        };
+        if opts.confidence || opts.record_history {
+            self.record_review(id, correct, confident);
+        }
+
+        let ticks = self.grade(id, correct, true, Local::now());
+        if let Some(path) = &opts.transcript {
+            self.write_transcript(path, id, correct, ticks);
+        }
+
+        let result = if correct {
+            "ok".green()
+        } else {
+            "failed".red()
+        };
+        if opts.quiet {
+            writeln!(out, "{}.", result).unwrap();
+        } else {
+            write!(out, "{}. ", result).unwrap();
+        }
+        if ticks == 0 {
+            if !opts.quiet {
+                if !self.is_done(&self.status[&id]) {
+                    writeln!(out, "due in {} days.", self.status[&id].days_left()).unwrap();
+                } else {
+                    writeln!(out, "card is {}!", "done".green()).unwrap();
+                }
+            }
+            self.played.insert(id);
+            if !correct {
+                self.wrong.insert(id);
+            }
+            self.save_log();
+        } else if !self.status[&id].is_due() {
+            if !opts.quiet {
+                writeln!(
+                    out,
+                    "next step in {} minutes.",
+                    self.config.learning_steps[self.status[&id].step]
+                )
+                .unwrap();
+            }
+            self.save_log();
+        } else if !opts.quiet {
+            writeln!(out, "{} ticks left.", ticks).unwrap();
+        }
+        if let Some(note) = &self.cards[&id].note {
+            writeln!(out, "{}", note.dimmed()).unwrap();
+        }
+
+        writeln!(out).unwrap();
+        true
+    }
+
+    pub fn is_done(&self, status: &Status) -> bool {
+        status.factor >= MAX_DAYS && status.reviews >= self.config.min_reviews_done
+    }
+
+    pub fn get_due(&self) -> Vec<usize> {
+        self.get_due_within(0)
+    }
+
+    pub fn get_due_within(&self, days: u32) -> Vec<usize> {
+        let mut old = self
+            .ids
+            .iter()
+            .copied()
+            .filter(|id| {
+                self.status
+                    .get(id)
+                    .map(|status| {
+                        status.is_due_within(days) && !status.is_new() && !self.is_done(status)
+                    })
+                    .unwrap_or(false)
+            })
+            .collect::<Vec<_>>();
+        old.sort_by_key(|id| self.status[id].timestamp);
+        old
+    }
+
+    // learned cards that are not due yet, soonest first.
+    pub fn get_upcoming(&self, ahead: u32) -> Vec<usize> {
+        let mut upcoming = self
+            .ids
+            .iter()
+            .copied()
+            .filter(|id| {
+                self.status
+                    .get(id)
+                    .map(|status| {
+                        status.ticks > 0
+                            && !status.is_new()
+                            && !status.is_due_within(ahead)
+                            && !self.is_done(status)
+                    })
+                    .unwrap_or(false)
+            })
+            .collect::<Vec<_>>();
+        upcoming.sort_by_key(|id| self.status[id].timestamp);
+        upcoming
+    }
+
+    pub fn get_done(&self) -> Vec<usize> {
+        let mut done = self
+            .ids
+            .iter()
+            .copied()
+            .filter(|id| {
+                self.status
+                    .get(id)
+                    .map(|status| self.is_done(status))
+                    .unwrap_or(false)
+            })
+            .collect::<Vec<_>>();
+        done.sort_by_key(|id| self.status[id].timestamp);
+        done
+    }
+
+    // learned cards that are neither due nor done. with new, due and done, every
+    // card is in exactly one of these.
+    pub fn get_waiting(&self) -> Vec<usize> {
+        self.ids
+            .iter()
+            .copied()
+            .filter(|id| {
+                self.status.get(id).is_some_and(|status| {
+                    !status.is_new() && !status.is_due() && !self.is_done(status)
+                })
+            })
+            .collect()
+    }
+
+    pub fn get_new(&self) -> Vec<usize> {
+        let mut new = self
+            .ids
+            .iter()
+            .copied()
+            .filter(|id| {
+                self.status
+                    .get(id)
+                    .map(|status| status.is_new())
+                    .unwrap_or(true)
+            })
+            .collect::<Vec<_>>();
+        new.sort_by_key(|id| {
+            let priority = self.cards.get(id).map(Card::priority);
+            (priority.unwrap_or(Priority::Normal), *id)
+        });
+        new
+    }
+
+    // in file order, due or not.
+    pub fn get_pinned(&self) -> Vec<usize> {
+        self.ids
+            .iter()
+            .copied()
+            .filter(|id| self.cards.get(id).is_some_and(Card::is_pinned))
+            .collect()
+    }
+
+    // marks the new cards, or only those tagged `tag`, as learned with the given
+    // factor, due that many days from now. returns how many were seeded.
+    pub fn seed_known(&mut self, tag: Option<&str>, factor: f64) -> usize {
+        let ids = self
+            .get_new()
+            .into_iter()
+            .filter(|id| tag.is_none_or(|tag| self.cards[id].tags.iter().any(|t| t == tag)))
+            .collect::<Vec<_>>();
+        for &id in ids.iter() {
+            self.set_status(
+                id,
+                Status {
+                    timestamp: Local::now().timestamp() + (86400.0 * factor) as i64,
+                    factor,
+                    ticks: 1,
+                    ..Status::new(id)
+                },
+            );
+        }
+        ids.len()
+    }
+
+    // brings the factors of learned cards into [1, cap], e.g. for a deck shared with
+    // someone else's log. a capped card is due within `cap` days. returns how many
+    // statuses changed.
+    pub fn normalize_factors(&mut self, cap: f64) -> usize {
+        let latest = Local::now().timestamp() + (86400.0 * cap) as i64;
+        let mut changed = 0;
+        for status in self.status.values_mut().filter(|status| !status.is_new()) {
+            let factor = status.factor.clamp(1.0, cap);
+            if factor != status.factor {
+                status.factor = factor;
+                status.timestamp = status.timestamp.min(latest);
+                changed += 1;
+            }
+        }
+        changed
+    }
+
+    // (min, median, max) factor of the learned cards.
+    pub fn factor_summary(&self) -> Option<(f64, f64, f64)> {
+        let mut factors = self
+            .status
+            .values()
+            .filter(|status| !status.is_new())
+            .map(|status| status.factor)
+            .collect::<Vec<_>>();
+        factors.sort_by(f64::total_cmp);
+        Some((
+            *factors.first()?,
+            factors[factors.len() / 2],
+            *factors.last()?,
+        ))
+    }
+
+    // statuses whose due date a wrong clock put out of reach: further ahead than
+    // twice the longest interval, or before the card was added. they are made due
+    // `factor` days from now, or from when the card was added. returns their ids.
+    pub fn fix_timestamps(&mut self) -> Vec<usize> {
+        let now = Local::now().timestamp();
+        let latest = now + (86400.0 * MAX_DAYS * 2.0) as i64;
+        let mut fixed = vec![];
+        for (&id, status) in self.status.iter_mut() {
+            let added = self.added.get(&id).copied().unwrap_or(0);
+            let from = if status.timestamp > latest {
+                now
+            } else if status.timestamp < added {
+                added
+            } else {
+                continue;
+            };
+            status.timestamp = from + (86400.0 * status.factor) as i64;
+            fixed.push(id);
+        }
+        fixed.sort();
+        fixed
+    }
+
+    // makes a card due right away. a new card becomes learned with factor 1.
+    // returns false if there is no such card.
+    pub fn make_due(&mut self, id: usize) -> bool {
+        if !self.cards.contains_key(&id) {
+            return false;
+        }
+        let now = Local::now().timestamp();
+        let status = self.status.entry(id).or_insert_with(|| Status::new(id));
+        if status.is_new() {
+            status.factor = 1.0;
+            status.step = 0;
+        }
+        status.timestamp = now;
+        status.ticks = status.ticks.max(1);
+        true
+    }
+
+    pub fn backup_deck(&self) -> Option<PathBuf> {
+        self.backup_file(&self.path, "")
+    }
+
+    pub fn backup_log(&self) -> Option<PathBuf> {
+        self.backup_file(&self.log_path, ".log")
+    }
+
+    // the backup a session takes as it starts, kept apart from other log backups
+    // so that --replay can tell when the last session began.
+    pub fn backup_session_log(&self) -> Option<PathBuf> {
+        self.backup_file(&self.log_path, SESSION_LOG_SUFFIX)
+    }
+
+    fn backup_file(&self, path: &Path, suffix: &str) -> Option<PathBuf> {
+        if self.read_only {
+            return None;
+        }
+        backup_file(path, &self.path, suffix)
+    }
+
+    pub fn add_cards(&self, cards: &str) -> Result<(), DeckErr> {
+        let cards = cards
+            .lines()
+            .enumerate()
+            .map(|(line, s)| (line + 1, s.trim()))
+            .filter(|(_, s)| !s.is_empty() && !s.starts_with('#'))
+            .collect::<Vec<_>>();
+        if cards.is_empty() {
+            return Ok(());
+        }
+        // everything is checked before anything is written, so the deck is never
+        // half appended to. ids from INCLUDE_ID_OFFSET on belong to includes, so
+        // new ones stay below it.
+        if self
+            .highest_id
+            .checked_add(cards.len())
+            .is_none_or(|last| last >= INCLUDE_ID_OFFSET)
+        {
+            return Err(DeckErr::IdOverflow {
+                id: self.highest_id,
+            });
+        }
+        // an empty deck takes its width from the first card.
+        let mut expected_size = (self.fields > 0).then(|| self.fields - 1);
+        for (i, &(line, text)) in cards.iter().enumerate() {
+            let card =
+                Card::parse_with_width(&format!("0 | {}", text), expected_size).map_err(|err| {
+                    DeckErr::BadCard {
+                        line,
+                        content: text.to_string(),
+                        err,
+                    }
+                })?;
+            let size = card.cues.len();
+            if *expected_size.get_or_insert(size) != size {
+                return Err(DeckErr::InconsistentNumberOfFields {
+                    id: i + self.highest_id + 1,
+                    line,
+                    content: text.to_string(),
+                    size,
+                    expected_size: expected_size.unwrap(),
+                });
+            }
+        }
+        let mut f = append_to(&self.path).map_err(|_| DeckErr::ReadOnly)?;
+        let mut added = append_to(&added_path_of(&self.path)).map_err(|_| DeckErr::ReadOnly)?;
+        self.backup_deck();
+        let now = Local::now().timestamp();
+        for (i, (_, card)) in cards.into_iter().enumerate() {
+            let id = i + self.highest_id + 1;
+            writeln!(f, "{} | {}", id, card).map_err(|_| DeckErr::ReadOnly)?;
+            writeln!(added, "{},{}", id, now).map_err(|_| DeckErr::ReadOnly)?;
+        }
+        Ok(())
+    }
+
+    // asks for the cues missing from cards meant for add_cards, labelled by the
+    // header. other lines are kept as they are, as is the rest once input ends.
+    pub fn fill_missing_fields(
+        &self,
+        cards: &str,
+        input: &mut dyn BufRead,
+        out: &mut dyn Write,
+    ) -> String {
+        // an empty deck has no width yet: the first card added sets it.
+        if self.fields == 0 {
+            return cards.to_string();
+        }
+        let mut filled = String::new();
+        let mut eof = false;
+        for line in cards.lines() {
+            let card = Card::from_str(&format!("0 | {}", line.trim()));
+            let mut card = match card {
+                Ok(card)
+                    if !eof
+                        && !line.trim().starts_with('#')
+                        && card.cues.len() < self.fields - 1 =>
+                {
+                    card
+                }
+                _ => {
+                    filled.push_str(line);
+                    filled.push('\n');
+                    continue;
+                }
+            };
+            writeln!(out, "{}", card.answer).unwrap();
+            while card.cues.len() < self.fields - 1 {
+                let i = card.cues.len();
+                let label = self
+                    .header
+                    .as_ref()
+                    .map(|h| h.cues[i].clone())
+                    .filter(|s| !s.is_empty())
+                    .unwrap_or_else(|| format!("cue {}", i + 1));
+                write!(out, "{}: ", label.blue()).unwrap();
+                out.flush().unwrap();
+                let mut cue = String::new();
+                if input.read_line(&mut cue).unwrap_or(0) == 0 {
+                    eof = true;
+                    break;
+                }
+                card.cues.push(cue.trim().to_string());
+            }
+            let card = card.to_string();
+            filled.push_str(card.strip_prefix("0 | ").unwrap_or(&card));
+            filled.push('\n');
+        }
+        filled
+    }
+
+    // `id,timestamp,correct[,confident]`, the last only with --confidence.
+    fn record_review(&self, id: usize, correct: bool, confident: Option<bool>) {
+        if self.read_only {
+            return;
+        }
+        let path = history_path_of(&self.path);
+        let mut line = format!("{},{},{}", id, Local::now().timestamp(), u8::from(correct));
+        if let Some(confident) = confident {
+            line += &format!(",{}", u8::from(confident));
+        }
+        if let Err(err) = append_to(&path).and_then(|mut f| writeln!(f, "{}", line)) {
+            eprintln!(
+                "warning: could not write {}: {}",
+                path.to_string_lossy(),
+                err
+            );
+        }
+    }
+
+    // one readable line per answer, for --log-session.
+    fn write_transcript(&self, path: &Path, id: usize, correct: bool, ticks: usize) {
+        let card = &self.cards[&id];
+        let status = &self.status[&id];
+        let next = if ticks == 0 && self.is_done(status) {
+            "done".to_string()
+        } else if ticks == 0 {
+            format!("due in {} days", status.days_left())
+        } else if !status.is_due() {
+            format!(
+                "next step in {} minutes",
+                self.config.learning_steps[status.step]
+            )
+        } else {
+            format!("{} ticks left", ticks)
+        };
+        let line = format!(
+            "{} {}::#{}: {} -> {}: {}, {}.",
+            Local::now().format("%Y-%m-%d %H:%M"),
+            self.path.to_string_lossy(),
+            id,
+            card.cues
+                .iter()
+                .filter(|cue| !cue.is_empty())
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(" / "),
+            card.answer,
+            if correct { "ok" } else { "failed" },
+            next
+        );
+        if let Err(err) = append_to(path).and_then(|mut f| writeln!(f, "{}", line)) {
+            eprintln!(
+                "warning: could not write {}: {}",
+                path.to_string_lossy(),
+                err
+            );
+        }
+    }
+
+    // updates the status of `id` for an answer. returns the ticks left.
+    fn grade(&mut self, id: usize, correct: bool, randomize: bool, now: DateTime<Local>) -> usize {
+        let was_done = self
+            .status
+            .get(&id)
+            .is_some_and(|status| self.is_done(status));
+        let status = self.status.entry(id).or_insert_with(|| Status {
+            timestamp: now.timestamp(),
+            ..Status::new(id)
+        });
+        let ticks = status.update(correct, randomize, &self.config, now);
+        // a done card, e.g. from --review-done, that was forgotten goes back into rotation.
+        if was_done && !correct && status.factor >= MAX_DAYS {
+            status.factor = MAX_DAYS / 2.0;
+            status.timestamp = now.timestamp() + (86400.0 * status.factor) as i64;
+        }
+        ticks
+    }
+
+    // grades the cards again as in `history`, in the format of the history file,
+    // on top of the statuses in memory and without the random part of intervals.
+    // each answer is graded at the time it was given. lines from before `since`,
+    // when the statuses were taken, are left out. nothing is saved. returns the
+    // numbers of the lines that were skipped and how many were left out.
+    pub fn replay(&mut self, history: &str, since: i64) -> (Vec<usize>, usize) {
+        let mut skipped = vec![];
+        let mut earlier = 0;
+        for (i, line) in history.lines().enumerate() {
+            let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+            let grade = match fields[..] {
+                [id, timestamp, correct, ..] => {
+                    let correct = match correct {
+                        "1" => Some(true),
+                        "0" => Some(false),
+                        _ => None,
+                    };
+                    let answered = timestamp
+                        .parse::<i64>()
+                        .ok()
+                        .and_then(|timestamp| Local.timestamp_opt(timestamp, 0).single());
+                    id.parse::<usize>().ok().zip(correct).zip(answered)
+                }
+                _ => None,
+            };
+            let Some(((id, correct), answered)) =
+                grade.filter(|((id, _), _)| self.cards.contains_key(id))
+            else {
+                skipped.push(i + 1);
+                continue;
+            };
+            if answered.timestamp() < since {
+                earlier += 1;
+                continue;
+            }
+            if let Some(status) = self.status.get_mut(&id) {
+                // as in a session, a card played to the end can be played again.
+                status.ticks = status.ticks.max(1);
+            }
+            self.grade(id, correct, false, answered);
+        }
+        (skipped, earlier)
+    }
+
+    // cards answered correctly at least once without feeling sure, by how often.
+    pub fn unsure_cards(&self) -> Vec<(usize, usize)> {
+        let history = std::fs::read_to_string(history_path_of(&self.path)).unwrap_or_default();
+        let mut counts = HashMap::<usize, usize>::new();
+        for line in history.lines() {
+            let fields = line.split(',').map(str::trim).collect::<Vec<_>>();
+            if let [id, _, "1", "0"] = fields[..] {
+                if let Ok(id) = id.parse() {
+                    *counts.entry(id).or_default() += 1;
+                }
+            }
+        }
+        let mut unsure = counts
+            .into_iter()
+            .filter(|(id, _)| self.cards.contains_key(id))
+            .collect::<Vec<_>>();
+        unsure.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+        unsure
+    }
+
+    pub fn added_date(&self, id: usize) -> Option<NaiveDate> {
+        self.added
+            .get(&id)
+            .map(|&timestamp| Local.timestamp_opt(timestamp, 0).unwrap().date_naive())
+    }
+
+    // prints everything known about a card. returns false if there is no such card.
+    pub fn peek(&self, id: usize, out: &mut dyn Write, date_format: &str) -> bool {
+        let Some(card) = self.cards.get(&id) else {
+            return false;
+        };
+        writeln!(out, "{}::#{}", self.path.to_string_lossy().green(), id).unwrap();
+        let answer_header = self
+            .header
+            .as_ref()
+            .map(|h| h.answer.clone())
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| "answer".to_string());
+        writeln!(out, "{}: {}", answer_header.blue(), card.answer).unwrap();
+        for (i, cue) in card.cues.iter().enumerate() {
+            let header = self
+                .header
+                .as_ref()
+                .map(|h| h.cues[i].clone())
+                .filter(|s| !s.is_empty())
+                .unwrap_or_else(|| "cue".to_string());
+            writeln!(out, "{}: {}", header.blue(), cue).unwrap();
+        }
+        match self.status.get(&id) {
+            Some(status) if !status.is_new() => writeln!(
+                out,
+                "due {} (factor {:.2}, {} reviews)",
+                format_date(status.due_date(), date_format),
+                status.factor,
+                status.reviews
+            )
+            .unwrap(),
+            _ => writeln!(out, "new").unwrap(),
+        }
+        if let Some(date) = self.added_date(id) {
+            writeln!(out, "added {}", format_date(date, date_format)).unwrap();
+        }
+        true
+    }
+
+    pub fn dump(&self, w: &mut dyn Write, date_format: &str) -> std::io::Result<()> {
+        for (card, status) in self.iter_cards() {
+            let status = status.copied().unwrap_or_else(|| Status::new(card.id));
+            writeln!(
+                w,
+                "{},{},{},{:.2}",
+                card.id,
+                card.answer,
+                format_date(status.due_date(), date_format),
+                status.factor,
+            )?;
+        }
+        Ok(())
+    }
+
+    // `other` is taken as the newer version. fields are compared with runs of
+    // whitespace collapsed, so realigning columns is not a change.
+    pub fn diff(&self, other: &Deck) -> DeckDiff {
+        let normalize = |card: &Card| {
+            std::iter::once(&card.answer)
+                .chain(card.cues.iter())
+                .map(|field| field.split_whitespace().collect::<Vec<_>>().join(" "))
+                .collect::<Vec<_>>()
+        };
+        let mut diff = DeckDiff {
+            added: other
+                .ids
+                .iter()
+                .copied()
+                .filter(|id| !self.cards.contains_key(id))
+                .collect(),
+            removed: self
+                .ids
+                .iter()
+                .copied()
+                .filter(|id| !other.cards.contains_key(id))
+                .collect(),
+            changed: vec![],
+        };
+        for (old, _) in self.iter_cards() {
+            if let Some(new) = other.cards.get(&old.id) {
+                if normalize(old) != normalize(new) {
+                    diff.changed
+                        .push((old.id, old.to_string(), new.to_string()));
+                }
+            }
+        }
+        diff.added.sort();
+        diff.removed.sort();
+        diff.changed.sort();
+        diff
+    }
+
+    // the header and cards with only their answers and cues, for sharing. with
+    // renumber, ids count up from 1 in file order.
+    // with trim_empty_cues, trailing cue columns that are empty for every card
+    // are left out, header included, so the export still parses.
+    pub fn export_stripped(
+        &self,
+        w: &mut dyn Write,
+        renumber: bool,
+        trim_empty_cues: bool,
+    ) -> std::io::Result<()> {
+        let width = if trim_empty_cues {
+            self.iter_cards()
+                .map(|(card, _)| {
+                    card.cues
+                        .iter()
+                        .rposition(|cue| !cue.is_empty())
+                        .map_or(1, |i| i + 1)
+                })
+                .max()
+                .unwrap_or(1)
+        } else {
+            usize::MAX
+        };
+        let strip = |card: &Card, id| Card {
+            id,
+            answer: card.answer.clone(),
+            cues: card.cues.iter().take(width).cloned().collect(),
+            tags: vec![],
+            note: None,
+        };
+        if let Some(header) = &self.header {
+            writeln!(w, "{}", strip(header, 0))?;
+        }
+        for (n, (card, _)) in self.iter_cards().enumerate() {
+            let id = if renumber { n + 1 } else { card.id };
+            writeln!(w, "{}", strip(card, id))?;
+        }
+        Ok(())
+    }
+
+    // the deck's part of a study sheet: a <section> with a printable table of
+    // cues and answers. with hide_answers the answer column is left blank and the
+    // answers follow on a page of their own.
+    pub fn export_study_sheet_section(
+        &self,
+        w: &mut dyn Write,
+        hide_answers: bool,
+    ) -> std::io::Result<()> {
+        let (cue_header, answer_header) = match &self.header {
+            Some(h) => (
+                h.cues
+                    .iter()
+                    .filter(|s| !s.is_empty())
+                    .cloned()
+                    .collect::<Vec<_>>()
+                    .join(" / "),
+                h.answer.clone(),
+            ),
+            None => ("cue".to_string(), "answer".to_string()),
+        };
+        let cues = |card: &Card| {
+            card.cues
+                .iter()
+                .filter(|s| !s.is_empty())
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(" / ")
+        };
+
+        writeln!(w, "<section>")?;
+        writeln!(w, "<h1>{}</h1>", escape_html(&self.path.to_string_lossy()))?;
+        writeln!(w, "<table>")?;
+        writeln!(
+            w,
+            "<tr><th>#</th><th>{}</th><th>{}</th></tr>",
+            escape_html(&cue_header),
+            escape_html(&answer_header)
+        )?;
+        for (n, (card, _)) in self.iter_cards().enumerate() {
+            let answer = if hide_answers {
+                String::new()
+            } else {
+                escape_html(&card.answer)
+            };
+            writeln!(
+                w,
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                n + 1,
+                escape_html(&cues(card)),
+                answer
+            )?;
+        }
+        writeln!(w, "</table>")?;
+        if hide_answers {
+            writeln!(w, "<div style=\"page-break-before: always\">")?;
+            writeln!(w, "<h2>{}</h2>", escape_html(&answer_header))?;
+            writeln!(w, "<ol>")?;
+            for (card, _) in self.iter_cards() {
+                writeln!(w, "<li>{}</li>", escape_html(&card.answer))?;
+            }
+            writeln!(w, "</ol>")?;
+            writeln!(w, "</div>")?;
+        }
+        writeln!(w, "</section>")?;
+        Ok(())
+    }
+
+    // every status, sorted by id, so that logs from different machines diff cleanly.
+    pub fn export_log(&self, w: &mut dyn Write) -> std::io::Result<()> {
+        let mut ids = self.status.keys().collect::<Vec<_>>();
+        ids.sort();
+        for id in ids {
+            writeln!(w, "{:.*}", self.config.factor_precision, self.status[id])?;
+        }
+        Ok(())
+    }
+
+    // for each id, keeps the status with the latest timestamp, i.e. the latest review.
+    pub fn merge_logs(
+        local: &HashMap<usize, Status>,
+        incoming: &HashMap<usize, Status>,
+    ) -> HashMap<usize, Status> {
+        let mut merged = local.clone();
+        for (&id, status) in incoming.iter() {
+            match merged.get(&id) {
+                Some(local) if local.timestamp >= status.timestamp => {}
+                _ => {
+                    merged.insert(id, *status);
+                }
+            }
+        }
+        merged
+    }
+
+    // drops all but the k lowest (or, with from_end, highest) ids. only meant for
+    // display: the log is not touched, but saving it afterwards would lose cards.
+    pub fn keep_n(&mut self, k: usize, from_end: bool) {
+        let mut ids = self.ids.clone();
+        ids.sort();
+        if from_end {
+            ids.reverse();
+        }
+        let keep = ids.into_iter().take(k).collect::<HashSet<_>>();
+        self.keep_ids(|id| keep.contains(&id));
+    }
+
+    // drops the other cards from this in-memory copy, e.g. for --ids. their
+    // statuses are kept, so the log still round-trips.
+    pub fn keep_ids(&mut self, keep: impl Fn(usize) -> bool) {
+        self.cards.retain(|&id, _| keep(id));
+        self.ids.retain(|&id| keep(id));
+    }
+
+    pub fn stats(&self, days: usize) -> Stats {
+        let mut forecast = vec![0; days];
+        for status in self.ids.iter().filter_map(|id| self.status.get(id)) {
+            if status.is_new() || self.is_done(status) {
+                continue;
+            }
+            if let Some(count) = forecast.get_mut(status.days_left().max(0) as usize) {
+                *count += 1;
+            }
+        }
+        Stats {
+            new: self.get_new().len(),
+            due: self.get_due().len(),
+            done: self.get_done().len(),
+            total: self.ids.len(),
+            forecast,
+        }
+    }
+
+    pub fn inspect(&self) {
+        let new = self.get_new().len();
         println!(
-            "{}: {} due, {} new{}, {} done, {} total",
+            "{}: {} due, {} new{}, {} waiting, {} done, {} total",
             self.path.to_string_lossy(),
             self.get_due().len(),
             new,
             if new > 0 {
                 format!(
                     " (#{})",
-                    self.cards
-                        .keys()
+                    self.ids
+                        .iter()
                         .filter(|id| {
                             self.status
                                 .get(id)
@@ -371,83 +1939,876 @@ impl Deck {
             } else {
                 "".to_string()
             },
+            self.get_waiting().len(),
             self.get_done().len(),
-            self.cards.len()
-        );
-    }
-
-    pub fn save_log(&self) {
-        // eprint!("saving log... ");
-        let mut f = File::options()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&self.log_path)
-            .unwrap_or_else(|_| panic!("could not open {}", self.log_path.to_string_lossy()));
-        // let mut count = 0;
-        for id in &self.ids {
-            if let Some(status) = self.status.get(id) {
-                // count += 1;
-                f.write_all(
-                    format!("{},{},{:.2}\n", status.id, status.timestamp, status.factor).as_bytes(),
-                )
-                .expect("could not write to file");
+            self.ids.len()
+        );
+    }
+
+    // the files --renumber rewrites, with their new contents: the deck file, its
+    // log, and the `.added` and `.history` sidecars if there are any. the deck's
+    // own cards move to their new ids, and the statuses, added times and history
+    // of cards no longer in the deck are dropped. nothing is written.
+    pub fn renumber(&mut self) -> std::io::Result<Vec<(PathBuf, String)>> {
+        let (contents, new_ids) = renumber(&std::fs::read_to_string(&self.path)?);
+        // ids below INCLUDE_ID_OFFSET that are no longer in the deck are dropped,
+        // as a renumbered card may take them over.
+        let new_id = |id: usize| match new_ids.get(&id) {
+            Some(&new_id) => Some(new_id),
+            None if id < INCLUDE_ID_OFFSET => None,
+            None => Some(id),
+        };
+        self.ids = self.ids.iter().filter_map(|&id| new_id(id)).collect();
+        self.cards = std::mem::take(&mut self.cards)
+            .into_values()
+            .filter_map(|card| {
+                let id = new_id(card.id)?;
+                Some((id, Card { id, ..card }))
+            })
+            .collect();
+        self.status = std::mem::take(&mut self.status)
+            .into_values()
+            .filter_map(|status| {
+                let id = new_id(status.id)?;
+                Some((id, Status { id, ..status }))
+            })
+            .collect();
+        self.added = std::mem::take(&mut self.added)
+            .into_iter()
+            .filter_map(|(id, timestamp)| Some((new_id(id)?, timestamp)))
+            .collect();
+        let mut files = vec![
+            (self.path.clone(), contents),
+            (self.log_path.clone(), self.log_contents()),
+        ];
+        let added_path = added_path_of(&self.path);
+        if added_path.exists() {
+            let mut added = self.added.iter().collect::<Vec<_>>();
+            added.sort();
+            let added = added
+                .iter()
+                .map(|(id, timestamp)| format!("{},{}\n", id, timestamp))
+                .collect();
+            files.push((added_path, added));
+        }
+        let history_path = history_path_of(&self.path);
+        if let Ok(history) = std::fs::read_to_string(&history_path) {
+            let history = history
+                .lines()
+                .filter_map(|line| {
+                    let (id, rest) = line.split_once(',')?;
+                    let id = new_id(id.trim().parse::<usize>().ok()?)?;
+                    Some(format!("{},{}\n", id, rest))
+                })
+                .collect();
+            files.push((history_path, history));
+        }
+        Ok(files)
+    }
+
+    // the log as save_log would write it.
+    pub fn log_contents(&self) -> String {
+        let mut statuses = self
+            .ids
+            .iter()
+            .filter_map(|id| self.status.get(id))
+            .collect::<Vec<_>>();
+        match self.config.sort_log {
+            SortLog::Id => statuses.sort_by_key(|status| status.id),
+            SortLog::Timestamp => statuses.sort_by_key(|status| (status.timestamp, status.id)),
+            SortLog::File => {}
+        }
+        statuses
+            .iter()
+            .map(|status| format!("{:.*}\n", self.config.factor_precision, status))
+            .collect()
+    }
+
+    // returns whether the log was written. a log that can't be is warned about,
+    // e.g. on a read-only mount.
+    pub fn save_log(&self) -> bool {
+        if self.read_only {
+            return false;
+        }
+        if let Err(err) = std::fs::write(&self.log_path, self.log_contents()) {
+            eprintln!(
+                "warning: could not save {}: {}. progress is not saved.",
+                self.log_path.to_string_lossy(),
+                err
+            );
+            return false;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod test_deck {
+    use super::*;
+
+    #[test]
+    fn test_parse_good() {
+        let d = Deck::read_from_file(Path::new("tests/test_parse_ok.mnemo"), None).unwrap();
+        assert!(d.header.is_some());
+        assert_eq!(d.cards.len(), 5);
+
+        // cards
+        assert_eq!(d.cards[&1].answer, "Stockholm");
+        assert_eq!(d.cards[&2].cues, vec!["Norway", "O", ""]);
+
+        // status
+        assert_eq!(d.status[&1].factor, 1.0);
+        assert_eq!(d.status[&2].factor, 2.0);
+        assert_eq!(d.status[&3].factor, 3.0);
+        assert_eq!(d.status[&1].timestamp, 100000000);
+        assert_eq!(d.status[&2].timestamp, 200000000);
+        assert_eq!(d.status[&3].timestamp, 300000000);
+
+        // header
+        assert_eq!(d.header.as_ref().unwrap().answer, "Capital");
+        assert_eq!(
+            d.header.as_ref().unwrap().cues,
+            vec!["Country", "First letter", "Founded"]
+        );
+    }
+
+    #[test]
+    fn test_parse_inconsistent_number_of_fields() {
+        let d = Deck::read_from_file(
+            Path::new("tests/test_parse_inconsistent_number_of_fields.mnemo"),
+            None,
+        )
+        .unwrap_err();
+        assert_eq!(
+            d,
+            DeckErr::InconsistentNumberOfFields {
+                id: 3,
+                line: 4,
+                content: "3 | Washington DC | USA".to_string(),
+                size: 1,
+                expected_size: 3
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_directives() {
+        let d = Deck::read_from_file(Path::new("tests/test_learning_steps.mnemo"), None).unwrap();
+        assert_eq!(d.config.learning_steps, vec![1, 10]);
+        assert_eq!(d.cards.len(), 2);
+        assert_eq!(d.status[&2].step, 1);
+        assert!(d.status[&2].is_new());
+    }
+
+    #[test]
+    fn test_include() {
+        let d = Deck::read_from_file(Path::new("tests/test_include.mnemo"), None).unwrap();
+        assert_eq!(d.cards.len(), 7);
+        assert_eq!(d.highest_id, 2);
+        assert_eq!(d.header.as_ref().unwrap().answer, "Capital");
+
+        // own cards
+        assert_eq!(d.cards[&1].answer, "Madrid");
+        assert_eq!(d.status[&1].factor, 4.0);
+
+        // included cards, with statuses from the base log unless overridden
+        let base = INCLUDE_ID_OFFSET;
+        assert_eq!(d.cards[&(base + 1)].answer, "Stockholm");
+        assert_eq!(d.cards[&(base + 5)].answer, "Mogadishu");
+        assert_eq!(d.status[&(base + 1)].factor, 8.0);
+        assert_eq!(d.status[&(base + 2)].factor, 2.0);
+        assert_eq!(d.status[&(base + 2)].id, base + 2);
+        assert!(!d.status.contains_key(&(base + 4)));
+    }
+
+    #[test]
+    fn test_include_id_collision() {
+        // test_include.mnemo's own include lands on 2000001.., as does the second include.
+        assert_eq!(
+            Deck::read_from_file(Path::new("tests/test_include_nested.mnemo"), None).unwrap_err(),
+            DeckErr::IncludeIdCollision {
+                path: PathBuf::from("tests/test_parse_ok.mnemo"),
+                id: 2 * INCLUDE_ID_OFFSET + 1,
+            }
+        );
+        assert_eq!(
+            Deck::read_from_file(Path::new("tests/test_include_own_id.mnemo"), None).unwrap_err(),
+            DeckErr::IncludeIdCollision {
+                path: PathBuf::from("tests/test_parse_ok.mnemo"),
+                id: INCLUDE_ID_OFFSET + 2,
             }
+        );
+    }
+
+    #[test]
+    fn test_include_cycle() {
+        let err =
+            Deck::read_from_file(Path::new("tests/test_include_cycle_a.mnemo"), None).unwrap_err();
+        assert!(matches!(err, DeckErr::IncludeCycle(_)));
+    }
+
+    #[test]
+    fn test_id_overflow() {
+        let d = Deck::read_from_file(Path::new("tests/test_huge_ids.mnemo"), None).unwrap();
+        assert!(d.cards.contains_key(&(usize::MAX - 5)));
+        assert_eq!(
+            Deck::read_from_file(Path::new("tests/test_include_overflow.mnemo"), None).unwrap_err(),
+            DeckErr::IdOverflow { id: usize::MAX - 5 }
+        );
+
+        let mut d = Deck::new(Path::new("tests/test_id_overflow_copy.mnemo"), 2);
+        d.highest_id = usize::MAX - 1;
+        assert_eq!(
+            d.add_cards("a | A\nb | B\n"),
+            Err(DeckErr::IdOverflow { id: usize::MAX - 1 })
+        );
+        d.highest_id = INCLUDE_ID_OFFSET - 2;
+        assert!(matches!(
+            d.add_cards("a | A\nb | B\n"),
+            Err(DeckErr::IdOverflow { .. })
+        ));
+        assert!(!Path::new("tests/test_id_overflow_copy.mnemo").exists());
+    }
+
+    #[test]
+    fn test_play_card_quiet() {
+        const DECK_COPY: &str = "tests/test_play_card_quiet.mnemo";
+        std::fs::copy(Path::new("tests/test_parse_ok.mnemo"), Path::new(DECK_COPY)).unwrap();
+        let mut d = Deck::read_from_file(Path::new(DECK_COPY), None).unwrap();
+
+        let play = |d: &mut Deck, opts: &PlayOptions| {
+            let mut out = vec![];
+            assert!(d.play_card(4, None, opts, &mut "\ny\n".as_bytes(), &mut out));
+            String::from_utf8(out).unwrap()
+        };
+
+        let out = play(&mut d, &PlayOptions::default());
+        assert!(out.contains("test_play_card_quiet.mnemo::#4"));
+        assert!(out.contains("Country: Madagascar"));
+        assert!(out.contains("Capital: Antananarivo"));
+        assert!(out.contains("ok. 1 ticks left."));
+
+        let quiet = PlayOptions {
+            quiet: true,
+            ..PlayOptions::default()
+        };
+        let out = play(&mut d, &quiet);
+        assert!(!out.contains("::#4"));
+        assert!(out.contains("Country: Madagascar"));
+        assert!(out.contains("Capital: Antananarivo"));
+        assert!(out.contains("ok.\n"));
+        assert!(!out.contains("due in"));
+        assert!(d.played.contains(&4));
+
+        std::fs::remove_file(Path::new(DECK_COPY)).unwrap();
+        std::fs::remove_file(Path::new("tests/test_play_card_quiet.mnemo.log")).unwrap();
+    }
+
+    #[test]
+    fn test_play_card_give_up() {
+        use crate::suite::Suite;
+
+        let mut suite = Suite {
+            decks: vec![Deck::read_from_reader(&mut "1 | a | A\n2 | b | B\n".as_bytes()).unwrap()],
+        };
+        let opts = PlayOptions {
+            max_new: 2,
+            non_interactive: true,
+            ..PlayOptions::default()
+        };
+        let mut out = vec![];
+        suite
+            .play(&opts, &mut "x\n\ny\nq\n".as_bytes(), &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("answer: a\nfailed."));
+        assert_eq!(out.matches("correct?").count(), 1);
+        assert!(out.contains("answer: b\ncorrect? [y/n] ok."));
+        assert!(suite.decks[0].wrong.is_empty());
+        assert_eq!(suite.decks[0].status[&1].step, 0);
+        assert_eq!(suite.decks[0].status[&2].step, 1);
+    }
+
+    #[test]
+    fn test_play_card_typed() {
+        let mut d = Deck::read_from_reader(
+            &mut "# strip_parens: true\n1 | run (verb) | correr\n".as_bytes(),
+        )
+        .unwrap();
+        let opts = PlayOptions {
+            typed: true,
+            ..PlayOptions::default()
+        };
+        let mut out = vec![];
+        assert!(d.play_card(1, None, &opts, &mut "Run\n".as_bytes(), &mut out));
+        let out = String::from_utf8(out).unwrap();
+        // the typed line is not echoed here, so the prompt runs into the reveal.
+        assert!(out.contains("\nyour answer: "));
+        assert!(out.contains(" answer: run (verb)\nok."));
+        assert!(!out.contains("correct?"));
+
+        let mut out = vec![];
+        assert!(d.play_card(1, None, &opts, &mut "walk\n".as_bytes(), &mut out));
+        assert!(String::from_utf8(out).unwrap().contains("failed."));
+    }
+
+    #[test]
+    fn test_play_card_typed_list() {
+        colored::control::set_override(false);
+        let mut d = Deck::read_from_reader(
+            &mut "# list_separator: ;\n# list_threshold: 0.66\n1 | war; famine; plague | horsemen\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let opts = PlayOptions {
+            typed: true,
+            ..PlayOptions::default()
+        };
+        let mut out = vec![];
+        assert!(d.play_card(1, None, &opts, &mut "plague; War\n".as_bytes(), &mut out));
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("2/3 items.\nmissed: famine\nok."));
+
+        let mut out = vec![];
+        assert!(d.play_card(1, None, &opts, &mut "war; flood\n".as_bytes(), &mut out));
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("1/3 items.\nmissed: famine, plague\nfailed."));
+    }
+
+    #[test]
+    fn test_play_card_ease() {
+        colored::control::set_override(false);
+        let mut d =
+            Deck::read_from_reader(&mut "1 | Stockholm | Sweden\n2 | Oslo | Norway\n".as_bytes())
+                .unwrap();
+        for id in [1, 2] {
+            d.set_status(
+                id,
+                Status {
+                    timestamp: 100000000,
+                    factor: 4.0,
+                    ticks: 1,
+                    ..Status::new(id)
+                },
+            );
         }
-        // eprintln!("wrote {} lines", count);
+        let opts = PlayOptions::default();
+
+        let mut out = vec![];
+        assert!(d.play_card(1, None, &opts, &mut "\n+\n+\ny\n".as_bytes(), &mut out));
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("factor now 4.80.\n"));
+        assert!(out.contains("factor now 5.76.\n"));
+        // doubled, and at most 20% more at random.
+        let factor = d.status[&1].factor;
+        assert!((11.52..=11.52 * 1.2).contains(&factor));
+        assert!(d
+            .log_contents()
+            .contains(&format!("1,{},{:.2}", d.status[&1].timestamp, factor)));
+
+        let opts = PlayOptions {
+            ease_step: Some(1.0),
+            ..PlayOptions::default()
+        };
+        assert!(d.play_card(2, None, &opts, &mut "\n-\nn\n".as_bytes(), &mut vec![]));
+        // halved to 2, then halved again for the wrong answer.
+        assert!(d.status[&2].factor < 1.2);
+    }
+
+    #[test]
+    fn test_play_card_show_empty_cues() {
+        let mut d = Deck::read_from_reader(
+            &mut "0 | Capital | Country | Founded\n1 | Oslo | Norway |\n".as_bytes(),
+        )
+        .unwrap();
+        let mut out = vec![];
+        assert!(d.play_card(
+            1,
+            None,
+            &PlayOptions::default(),
+            &mut "\ny\n".as_bytes(),
+            &mut out
+        ));
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("Country: Norway\n"));
+        assert!(!out.contains("Founded"));
+
+        let opts = PlayOptions {
+            show_empty_cues: true,
+            ..PlayOptions::default()
+        };
+        let mut out = vec![];
+        assert!(d.play_card(1, None, &opts, &mut "\ny\n".as_bytes(), &mut out));
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .contains("Country: Norway\nFounded: \n"));
+    }
+
+    #[test]
+    fn test_play_card_scaffold() {
+        let mut d =
+            Deck::read_from_reader(&mut "1 | こんにちは世界 | hello world\n".as_bytes()).unwrap();
+        let opts = PlayOptions {
+            scaffold: true,
+            ..PlayOptions::default()
+        };
+        let mut out = vec![];
+        assert!(d.play_card(1, None, &opts, &mut "\ny\n".as_bytes(), &mut out));
+        let out = String::from_utf8(out).unwrap();
+        let hint = out
+            .lines()
+            .find_map(|line| line.strip_prefix("hint: "))
+            .unwrap();
+        assert_eq!(hint.chars().filter(|&c| c == '_').count(), 7);
+        assert!(out.find("hint: ").unwrap() < out.find("reveal... ").unwrap());
+    }
+
+    #[test]
+    fn test_play_card_note() {
+        let mut d =
+            Deck::read_from_reader(&mut "1 | Oslo | Norway | %since 1814\n".as_bytes()).unwrap();
+        assert_eq!(d.fields, 2);
+        let mut out = vec![];
+        assert!(d.play_card(
+            1,
+            None,
+            &PlayOptions::default(),
+            &mut "\ny\n".as_bytes(),
+            &mut out
+        ));
+        let out = String::from_utf8(out).unwrap();
+        let (before, after) = out.split_once("correct?").unwrap();
+        assert!(!before.contains("1814"));
+        assert!(after.contains("ticks left.\nsince 1814\n"));
+    }
+
+    #[test]
+    fn test_wait() {
+        let now = std::cell::Cell::new(Duration::from_secs(5));
+        let mut sleeps = vec![];
+        let waited = wait(Duration::from_millis(250), &mut || now.get(), &mut |d| {
+            sleeps.push(d);
+            now.set(now.get() + d);
+        });
+        assert_eq!(waited, Duration::from_millis(250));
+        assert_eq!(
+            sleeps,
+            vec![
+                Duration::from_millis(100),
+                Duration::from_millis(100),
+                Duration::from_millis(50)
+            ]
+        );
+
+        // a jump of the clock ends the wait early.
+        let mut sleeps = 0;
+        let waited = wait(Duration::from_secs(3), &mut || now.get(), &mut |_| {
+            sleeps += 1;
+            now.set(now.get() + Duration::from_secs(60));
+        });
+        assert_eq!(sleeps, 1);
+        assert_eq!(waited, Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_play_card_auto_advance() {
+        let mut d = Deck::read_from_reader(&mut "1 | a | A\n".as_bytes()).unwrap();
+        let opts = PlayOptions {
+            auto_advance: Some(0.0),
+            ..PlayOptions::default()
+        };
+        let mut out = vec![];
+        assert!(d.play_card(1, None, &opts, &mut "y\n".as_bytes(), &mut out));
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .contains("reveal in 0s... \nanswer: a\ncorrect?"));
+        assert_eq!(d.status[&1].ticks, 1);
+    }
+
+    #[test]
+    fn test_cues_to_speak() {
+        let mut d = Deck::read_from_reader(
+            &mut "# lang: en, fr, en\n1 | bread | pain | loaf\n2 | water | eau |\n".as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(d.cues_to_speak(1, "fr"), vec!["pain"]);
+        assert_eq!(d.cues_to_speak(1, "en"), vec!["loaf"]);
+        assert!(d.cues_to_speak(2, "en").is_empty());
+        assert!(d.cues_to_speak(1, "de").is_empty());
+
+        d.config.langs.clear();
+        assert_eq!(d.cues_to_speak(1, "de"), vec!["pain", "loaf"]);
+    }
+
+    #[test]
+    fn test_play_card_confidence() {
+        const DECK_COPY: &str = "tests/test_play_card_confidence_copy.mnemo";
+        const HISTORY: &str = "tests/test_play_card_confidence_copy.mnemo.history";
+        const LOG: &str = "tests/test_play_card_confidence_copy.mnemo.log";
+        std::fs::copy("tests/test_parse_ok.mnemo", DECK_COPY).unwrap();
+        std::fs::copy("tests/test_parse_ok.mnemo.log", LOG).unwrap();
+        let _ = std::fs::remove_file(HISTORY);
+        let mut d = Deck::read_from_file(Path::new(DECK_COPY), None).unwrap();
+        let opts = PlayOptions {
+            confidence: true,
+            ..PlayOptions::default()
+        };
+
+        let mut out = vec![];
+        assert!(d.play_card(4, None, &opts, &mut "\ny\nn\n".as_bytes(), &mut out));
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .contains("correct? [y/n] confident? [y/n] ok."));
+        assert!(d.play_card(5, None, &opts, &mut "\nn\nn\n".as_bytes(), &mut vec![]));
+        assert!(d.play_card(1, None, &opts, &mut "\ny\ny\n".as_bytes(), &mut vec![]));
+        assert!(d.play_card(
+            3,
+            None,
+            &PlayOptions::default(),
+            &mut "\ny\n".as_bytes(),
+            &mut vec![]
+        ));
+
+        let history = std::fs::read_to_string(HISTORY).unwrap();
+        let history = history
+            .lines()
+            .map(|line| {
+                let (id, rest) = line.split_once(',').unwrap();
+                (id, rest.split_once(',').unwrap().1)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(history, vec![("4", "1,0"), ("5", "0,0"), ("1", "1,1")]);
+        assert_eq!(d.unsure_cards(), vec![(4, 1)]);
+
+        // eof at the confidence prompt quits without grading.
+        assert!(!d.play_card(2, None, &opts, &mut "\ny\n".as_bytes(), &mut vec![]));
+        assert_eq!(d.status[&2].timestamp, 200000000);
+
+        std::fs::remove_file(DECK_COPY).unwrap();
+        std::fs::remove_file(HISTORY).unwrap();
+        std::fs::remove_file(LOG).unwrap();
+    }
+
+    #[test]
+    fn test_play_card_done() {
+        const DECK_COPY: &str = "tests/test_play_card_done_copy.mnemo";
+        const LOG_COPY: &str = "tests/test_play_card_done_copy.mnemo.log";
+        std::fs::copy("tests/test_review_done.mnemo", DECK_COPY).unwrap();
+        std::fs::copy("tests/test_review_done.mnemo.log", LOG_COPY).unwrap();
+        let mut d = Deck::read_from_file(Path::new(DECK_COPY), None).unwrap();
+        assert_eq!(d.get_done(), vec![1, 2, 4]);
+
+        assert!(d.play_card(
+            1,
+            None,
+            &PlayOptions::default(),
+            &mut "\ny\n".as_bytes(),
+            &mut vec![]
+        ));
+        assert!(d.is_done(&d.status[&1]));
+        assert!(d.play_card(
+            4,
+            None,
+            &PlayOptions::default(),
+            &mut "\nn\n".as_bytes(),
+            &mut vec![]
+        ));
+        assert!(!d.is_done(&d.status[&4]));
+        assert_eq!(d.status[&4].days_left(), 30);
+        assert_eq!(d.get_done(), vec![2, 1]);
+
+        std::fs::remove_file(DECK_COPY).unwrap();
+        std::fs::remove_file(LOG_COPY).unwrap();
+    }
+
+    #[test]
+    fn test_fill_missing_fields() {
+        colored::control::set_override(false);
+        let d = Deck::read_from_file(Path::new("tests/test_parse_ok.mnemo"), None).unwrap();
+        let mut out = vec![];
+        let filled = d.fill_missing_fields(
+            "Madrid | Spain\nLisbon | Portugal | L | 1147\n",
+            &mut "M\n1561\n".as_bytes(),
+            &mut out,
+        );
+        assert_eq!(
+            filled,
+            "Madrid | Spain | M | 1561\nLisbon | Portugal | L | 1147\n"
+        );
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Madrid\nFirst letter: Founded: "
+        );
+
+        // input ran out: the card stays short, for add_cards to reject.
+        let filled = d.fill_missing_fields("Madrid | Spain\n", &mut "M\n".as_bytes(), &mut vec![]);
+        assert_eq!(filled, "Madrid | Spain | M\n");
+
+        let empty = Deck::read_from_reader(&mut "".as_bytes()).unwrap();
+        assert_eq!(empty.fields, 0);
+        let mut out = vec![];
+        let filled = empty.fill_missing_fields("Madrid | Spain\n", &mut "M\n".as_bytes(), &mut out);
+        assert_eq!(filled, "Madrid | Spain\n");
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_add_no_cards() {
+        const DECK_COPY: &str = "tests/test_add_no_cards_copy.mnemo";
+        const ADDED: &str = "tests/test_add_no_cards_copy.mnemo.added";
+        let _ = std::fs::remove_file(ADDED);
+        std::fs::copy("tests/test_tags.mnemo", DECK_COPY).unwrap();
+
+        let mut out = vec![];
+        let cards = read_new_cards(&mut "".as_bytes(), &mut out, true);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "enter cards, one per line, Ctrl-D to finish:\n"
+        );
+        let d = Deck::read_from_file(Path::new(DECK_COPY), None).unwrap();
+        d.add_cards(&cards).unwrap();
+        let d = Deck::read_from_file(Path::new(DECK_COPY), None).unwrap();
+        assert_eq!(d.ids.len(), 4);
+        assert!(!Path::new(ADDED).exists());
+
+        // piped input needs no prompt.
+        let mut out = vec![];
+        assert_eq!(
+            read_new_cards(&mut "a | A\n".as_bytes(), &mut out, false),
+            "a | A\n"
+        );
+        assert!(out.is_empty());
+
+        std::fs::remove_file(DECK_COPY).unwrap();
+    }
+
+    // reads /proc to tell that the process is gone.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_stop_speaking() {
+        let child = Command::new("sleep").arg("10").spawn().unwrap();
+        let pid = child.id();
+        let start = Instant::now();
+        stop_speaking(Some(child));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        // reaped: not even a zombie is left.
+        assert!(!Path::new(&format!("/proc/{}", pid)).exists());
+        stop_speaking(None);
+    }
+
+    #[test]
+    fn test_play_card_ascii() {
+        colored::control::set_override(false);
+        let mut d = Deck::read_from_reader(
+            &mut "0 | Word | Meaning | Note\n1 | 日本 | Japan | | %にほん\n".as_bytes(),
+        )
+        .unwrap();
+        let opts = PlayOptions {
+            show_empty_cues: true,
+            ..PlayOptions::default()
+        };
+        let mut out = vec![];
+        assert!(d.play_card(1, None, &opts, &mut "\nn\n".as_bytes(), &mut out));
+        let out = String::from_utf8(out).unwrap();
+        let chrome = out.replace("日本", "").replace("にほん", "");
+        assert!(chrome.contains("failed."));
+        assert!(chrome.bytes().all(|b| b.is_ascii() && b != 0x1b));
+    }
+
+    #[test]
+    fn test_play_card_eof() {
+        let mut d = Deck::read_from_reader(&mut "1 | a | A\n".as_bytes()).unwrap();
+        let mut out = vec![];
+        assert!(!d.play_card(
+            1,
+            None,
+            &PlayOptions::default(),
+            &mut "\n".as_bytes(),
+            &mut out
+        ));
+        assert!(!d.status.contains_key(&1));
+    }
+
+    #[test]
+    fn test_play_card_keys() {
+        let mut d = Deck::read_from_reader(&mut "1 | a | A\n".as_bytes()).unwrap();
+        let opts = PlayOptions {
+            keys: "pass=space,fail=f".parse().unwrap(),
+            ..PlayOptions::default()
+        };
+        let mut out = vec![];
+        assert!(d.play_card(1, None, &opts, &mut "\ny\n \n".as_bytes(), &mut out));
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.matches("correct? [space/f] ").count(), 2);
+        assert!(out.contains("ok. 1 ticks left."));
+
+        let mut out = vec![];
+        assert!(d.play_card(1, None, &opts, &mut "\nf\n".as_bytes(), &mut out));
+        assert!(String::from_utf8(out).unwrap().contains("failed."));
+    }
+
+    #[test]
+    fn test_bad_line_content() {
+        // lines count from 1, comments and directives included.
+        let err = Deck::read_from_reader(&mut "1 | a | A\nx | b | B\n".as_bytes()).unwrap_err();
+        assert!(matches!(err, DeckErr::BadCard { line: 2, .. }));
+        assert_eq!(err.content(), Some("x | b | B"));
+
+        let err = Deck::read_from_reader(&mut "# sort_log: never\n1 | a\n".as_bytes()).unwrap_err();
+        assert!(matches!(err, DeckErr::BadDirective { line: 1, .. }));
+        assert_eq!(err.content(), Some("# sort_log: never"));
+
+        let err = parse_log("1,100,1.00\n2,100\n").unwrap_err();
+        assert!(matches!(err, DeckErr::BadStatus { line: 2, .. }));
+        assert_eq!(err.content(), Some("2,100"));
+
+        let deck = "# sort_log: id\n1 | a | A\n2 | b | B\n3 | c\n";
+        let err = Deck::read_from_reader(&mut deck.as_bytes()).unwrap_err();
+        assert!(matches!(
+            err,
+            DeckErr::InconsistentNumberOfFields { line: 4, .. }
+        ));
+        assert_eq!(err.content(), Some("3 | c"));
+        // --inspect reads only the ids, and finds the same line.
+        const DECK: &str = "tests/test_bad_line_content_copy.mnemo";
+        std::fs::write(DECK, deck).unwrap();
+        let err = Deck::read_status_only(Path::new(DECK), None).unwrap_err();
+        assert!(matches!(
+            err,
+            DeckErr::InconsistentNumberOfFields { line: 4, .. }
+        ));
+        assert_eq!(err.content(), Some("3 | c"));
+        std::fs::remove_file(DECK).unwrap();
+
+        assert_eq!(DeckErr::FileNotFound.content(), None);
+    }
+
+    #[test]
+    fn test_parse_header_field_mismatch() {
+        assert_eq!(
+            Deck::read_from_file(
+                Path::new("tests/test_parse_header_field_mismatch.mnemo"),
+                None
+            )
+            .unwrap_err(),
+            DeckErr::HeaderFieldMismatch {
+                header_fields: 2,
+                card_fields: 3
+            }
+        );
+        let mut contents = "1 | Oslo | Norway\n0 | Capital | Country | Letter\n".as_bytes();
+        assert_eq!(
+            Deck::read_from_reader(&mut contents).unwrap_err(),
+            DeckErr::HeaderFieldMismatch {
+                header_fields: 2,
+                card_fields: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_read_from_reader() {
+        let mut contents =
+            "0 | Capital | Country\n1 | Oslo | Norway\n2 | Rome | Italy\n".as_bytes();
+        let d = Deck::read_from_reader(&mut contents).unwrap();
+        assert!(d.read_only);
+        assert!(d.status.is_empty());
+        assert_eq!(d.header.as_ref().unwrap().answer, "Capital");
+        assert_eq!(d.cards[&2].cues, vec!["Italy"]);
+        assert_eq!(d.get_new(), vec![1, 2]);
+
+        let mut contents = "1 | Oslo | Norway\n2 | Rome\n".as_bytes();
+        assert!(matches!(
+            Deck::read_from_reader(&mut contents),
+            Err(DeckErr::InconsistentNumberOfFields { id: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_tags() {
+        let d = Deck::read_from_file(Path::new("tests/test_tags.mnemo"), None).unwrap();
+        assert_eq!(d.cards[&1].tags, vec!["europe", "nordic"]);
+        assert_eq!(d.cards[&1].cues, vec!["Sweden"]);
+        assert!(d.cards[&3].tags.is_empty());
+
+        // the first card sets the columns: within them `%` and `@` start cues.
+        let d = Deck::read_from_reader(
+            &mut "0 | Word | Handle\n1 | a | @alice\n2 | b | %b | @geo\n3 | c | c | %note\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(d.cards[&1].cues, vec!["@alice"]);
+        assert!(d.cards[&1].tags.is_empty());
+        assert_eq!(
+            (d.cards[&2].cues[0].as_str(), d.cards[&2].note.as_deref()),
+            ("%b", None)
+        );
+        assert_eq!(d.cards[&2].tags, vec!["geo"]);
+        assert_eq!(d.cards[&3].note.as_deref(), Some("note"));
     }
-}
 
-#[cfg(test)]
-mod test_deck {
-    use super::*;
+    #[test]
+    fn test_min_reviews_done() {
+        let d = Deck::read_from_file(Path::new("tests/test_min_reviews_done.mnemo"), None).unwrap();
+        assert_eq!(d.config.min_reviews_done, 5);
+        assert_eq!(d.status[&1].reviews, 2);
+        assert_eq!(d.status[&2].reviews, 5);
+        assert_eq!(d.get_due(), vec![1]);
+        assert_eq!(d.get_done(), vec![2]);
+    }
 
     #[test]
-    fn test_parse_good() {
-        let d = Deck::read_from_file(Path::new("tests/test_parse_ok.mnemo")).unwrap();
-        assert!(d.header.is_some());
-        assert_eq!(d.cards.len(), 5);
+    fn test_invalid_utf8() {
+        let d = Deck::read_from_file(Path::new("tests/test_invalid_utf8.mnemo"), None).unwrap();
+        assert_eq!(d.cards[&2].answer, "Bogot\u{FFFD}");
+        assert_eq!(d.cards[&3].answer, "Moskva");
+        assert_eq!(d.warnings.len(), 1);
+        assert!(d.warnings[0].contains("line 2"));
 
-        // cards
-        assert_eq!(d.cards[&1].answer, "Stockholm");
-        assert_eq!(d.cards[&2].cues, vec!["Norway", "O", ""]);
+        let d = Deck::read_from_file(Path::new("tests/test_parse_ok.mnemo"), None).unwrap();
+        assert!(d.warnings.is_empty());
+    }
 
-        // status
-        assert_eq!(d.status[&1].factor, 1.0);
-        assert_eq!(d.status[&2].factor, 2.0);
-        assert_eq!(d.status[&3].factor, 3.0);
-        assert_eq!(d.status[&1].timestamp, 100000000);
-        assert_eq!(d.status[&2].timestamp, 200000000);
-        assert_eq!(d.status[&3].timestamp, 300000000);
+    #[test]
+    fn test_date_format() {
+        let date = NaiveDate::from_ymd_opt(2024, 1, 3).unwrap();
+        assert_eq!(format_date(date, DEFAULT_DATE_FORMAT), "2024-01-03");
+        assert_eq!(format_date(date, "%d/%m/%Y"), "03/01/2024");
 
-        // header
-        assert_eq!(d.header.as_ref().unwrap().answer, "Capital");
-        assert_eq!(
-            d.header.as_ref().unwrap().cues,
-            vec!["Country", "First letter", "Founded"]
-        );
+        assert!(validate_date_format(DEFAULT_DATE_FORMAT));
+        assert!(validate_date_format("%d/%m/%Y"));
+        assert!(validate_date_format("%b %e"));
+        assert!(!validate_date_format("%Q"));
+        assert!(!validate_date_format("%Y %"));
+        assert!(!validate_date_format("%H:%M"));
     }
 
     #[test]
-    fn test_parse_inconsistent_number_of_fields() {
-        let d = Deck::read_from_file(Path::new(
-            "tests/test_parse_inconsistent_number_of_fields.mnemo",
-        ))
-        .unwrap_err();
+    fn test_export_log() {
+        let d = Deck::read_from_file(Path::new("tests/test_parse_ok.mnemo"), None).unwrap();
+        let mut out = vec![];
+        d.export_log(&mut out).unwrap();
         assert_eq!(
-            d,
-            DeckErr::InconsistentNumberOfFields {
-                id: 3,
-                line: 4,
-                size: 1,
-                expected_size: 3
-            }
+            String::from_utf8(out).unwrap(),
+            "1,100000000,1.00\n2,200000000,2.00\n3,300000000,3.00\n10,400000000,1.00\n"
         );
     }
 
+    #[test]
+    fn test_merge_logs() {
+        let local = parse_log("1,100,1.00\n2,500,2.00\n3,100,4.00\n").unwrap();
+        let incoming = parse_log("2,300,1.00\n3,200,8.00\n4,100,1.00\n").unwrap();
+        let merged = Deck::merge_logs(&local, &incoming);
+        assert_eq!(merged.len(), 4);
+        assert_eq!(merged[&1], local[&1]);
+        assert_eq!(merged[&2], local[&2]);
+        assert_eq!(merged[&3], incoming[&3]);
+        assert_eq!(merged[&4], incoming[&4]);
+    }
+
     #[test]
     fn test_get_due() {
-        let d = Deck::read_from_file(Path::new("tests/test_parse_ok.mnemo")).unwrap();
+        let d = Deck::read_from_file(Path::new("tests/test_parse_ok.mnemo"), None).unwrap();
         let old = d.get_due();
         assert_eq!(old.len(), 3);
         assert_eq!(d.cards[&old[0]].answer, "Stockholm");
@@ -455,15 +2816,730 @@ mod test_deck {
         assert_eq!(d.cards[&old[2]].answer, "Washington DC");
     }
 
+    #[test]
+    fn test_stats() {
+        let mut d = Deck::read_from_file(Path::new("tests/test_parse_ok.mnemo"), None).unwrap();
+        d.status.get_mut(&3).unwrap().timestamp = Local::now().timestamp() + 2 * 86400;
+        let stats = d.stats(3);
+        assert_eq!(
+            stats,
+            Stats {
+                new: 2,
+                due: 2,
+                done: 0,
+                total: 5,
+                forecast: vec![2, 0, 1],
+            }
+        );
+
+        let mut out = vec![];
+        let date = NaiveDate::from_ymd_opt(2024, 1, 31).unwrap();
+        stats.write_csv(&mut out, date, "deck").unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "2024-01-31,deck,2,2,0,5,0,2\n\
+             2024-01-31,deck,2,2,0,5,1,0\n\
+             2024-01-31,deck,2,2,0,5,2,1\n"
+        );
+        let mut out = vec![];
+        stats.write_csv(&mut out, date, "a, \"b\".mnemo").unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .starts_with("2024-01-31,\"a, \"\"b\"\".mnemo\",2,"));
+    }
+
+    #[test]
+    fn test_suspicious_cards() {
+        let d = Deck::read_from_file(Path::new("tests/test_suspicious.mnemo"), None).unwrap();
+        assert_eq!(d.suspicious_cards(), vec![2, 4]);
+        assert_eq!(
+            d.warnings,
+            vec!["answer repeated in a cue, cards #2, #4".to_string()]
+        );
+
+        let d = Deck::read_from_file(Path::new("tests/test_parse_ok.mnemo"), None).unwrap();
+        assert!(d.suspicious_cards().is_empty());
+        assert!(d.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_log_dir() {
+        const LOG_DIR: &str = "tests/test_log_dir";
+        let deck = Path::new("tests/test_parse_ok.mnemo");
+        let log_dir = Path::new(LOG_DIR);
+        let log_path = log_dir.join(flat_name(deck) + ".log");
+        std::fs::create_dir_all(log_dir).unwrap();
+        std::fs::write(&log_path, "1,100000000,8.00\n").unwrap();
+
+        let mut d = Deck::read_from_file(deck, Some(log_dir)).unwrap();
+        assert_eq!(d.log_path, log_path);
+        assert_eq!(d.status.len(), 1);
+        assert_eq!(d.status[&1].factor, 8.0);
+        assert_eq!(
+            Deck::read_status_only(deck, Some(log_dir)).unwrap().status,
+            d.status
+        );
+
+        d.status
+            .insert(2, Status::from_str("2,100000000,2.00").unwrap());
+        d.save_log();
+        assert_eq!(
+            std::fs::read_to_string(&log_path).unwrap(),
+            "1,100000000,8.00\n2,100000000,2.00\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string("tests/test_parse_ok.mnemo.log").unwrap(),
+            "1,100000000,1.00\n2,200000000,2.00\n3,300000000,3.00\n10,400000000,1.00\n"
+        );
+
+        std::fs::remove_dir_all(log_dir).unwrap();
+    }
+
+    #[test]
+    fn test_make_due() {
+        let mut d = Deck::read_from_file(Path::new("tests/test_min_session.mnemo"), None).unwrap();
+        assert_eq!(d.get_due(), vec![1, 2]);
+        assert!(d.make_due(6));
+        assert_eq!(d.status[&6].factor, 2.0);
+        assert_eq!(d.get_due(), vec![1, 2, 6]);
+
+        let mut d = Deck::read_from_file(Path::new("tests/test_parse_ok.mnemo"), None).unwrap();
+        assert!(d.make_due(5));
+        assert!(!d.get_new().contains(&5));
+        assert!(d.get_due().contains(&5));
+        assert!(!d.make_due(42));
+    }
+
+    #[test]
+    fn test_buckets() {
+        let d = Deck::read_from_file(Path::new("tests/test_buckets.mnemo"), None).unwrap();
+        let (new, due, waiting, done) = (d.get_new(), d.get_due(), d.get_waiting(), d.get_done());
+        // a card still in its learning steps is new, even once its step is due.
+        assert_eq!(new, vec![4, 5]);
+        assert_eq!(due, vec![1]);
+        assert_eq!(waiting, vec![2]);
+        assert_eq!(done, vec![3]);
+        assert_eq!(
+            new.len() + due.len() + waiting.len() + done.len(),
+            d.ids.len()
+        );
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut d =
+            Deck::read_from_reader(&mut "1 | a | A\n2 | b | B\n3 | c | C\n".as_bytes()).unwrap();
+        d.set_status(
+            2,
+            Status {
+                timestamp: 0,
+                factor: 4.0,
+                ticks: 1,
+                ..Status::new(2)
+            },
+        );
+        // the first line is from an earlier session, and lines may lack confidence.
+        let history = "2,1699999000,0\n\
+                       1,1700000000,1,1\n2,1700000000,1,0\n1,1700000000,0\n\
+                       1,1700000000,1,1\n1,1700000000,1,1\n\
+                       3,1700000000,maybe,1\n9,1700000000,1,1\n";
+        assert_eq!(d.replay(history, 1_700_000_000), (vec![7, 8], 1));
+        // answers are graded on the day they were given.
+        let answered = Local.timestamp_opt(1_700_000_000, 0).unwrap().date_naive();
+        // new: through both learning steps after one slip.
+        assert_eq!(d.status[&1].factor, 1.0);
+        assert_eq!((d.status[&1].ticks, d.status[&1].step), (0, 0));
+        assert_eq!(d.status[&1].due_date(), answered + chrono::Days::new(1));
+        // learned: a right answer doubles the factor.
+        assert_eq!(d.status[&2].factor, 8.0);
+        assert_eq!(d.status[&2].reviews, 1);
+        assert_eq!(d.status[&2].due_date(), answered + chrono::Days::new(8));
+        assert!(!d.status.contains_key(&3));
+    }
+
+    #[test]
+    fn test_possible_swaps() {
+        let d = Deck::read_from_reader(
+            &mut "0 | Meaning | Word\n\
+                  1 | a large body of salt water | sea\n\
+                  2 | sprint | to run fast over a short distance\n\
+                  3 | a domestic animal | cat\n\
+                  4 | d | dog\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(d.possible_swaps(SwapThresholds::default()), vec![2, 4]);
+        let strict = SwapThresholds {
+            ratio: 20.0,
+            words: 4,
+        };
+        assert_eq!(d.possible_swaps(strict), vec![4]);
+
+        assert_eq!(
+            "3,5".parse(),
+            Ok(SwapThresholds {
+                ratio: 3.0,
+                words: 5
+            })
+        );
+        assert!("0.5,5".parse::<SwapThresholds>().is_err());
+        assert!("3".parse::<SwapThresholds>().is_err());
+    }
+
+    #[test]
+    fn test_backup() {
+        const DECK_COPY: &str = "tests/test_backup_copy.mnemo";
+        const LOG_COPY: &str = "tests/test_backup_copy.mnemo.log";
+        std::fs::copy("tests/test_parse_ok.mnemo", DECK_COPY).unwrap();
+        std::fs::copy("tests/test_parse_ok.mnemo.log", LOG_COPY).unwrap();
+
+        let d = Deck::read_from_file(Path::new(DECK_COPY), None).unwrap();
+        let deck_backup = d.backup_deck().unwrap();
+        let log_backup = d.backup_log().unwrap();
+        assert!(deck_backup.starts_with(BACKUP_DIR));
+        // taken in the same second, and still apart.
+        assert_ne!(deck_backup, log_backup);
+        assert_eq!(list_backups(Path::new(DECK_COPY), "")[0].0, deck_backup);
+        assert_eq!(list_backups(Path::new(DECK_COPY), ".log")[0].0, log_backup);
+        assert_eq!(
+            std::fs::read_to_string(&deck_backup).unwrap(),
+            std::fs::read_to_string(DECK_COPY).unwrap()
+        );
+        assert_eq!(
+            std::fs::read_to_string(&log_backup).unwrap(),
+            std::fs::read_to_string(LOG_COPY).unwrap()
+        );
+
+        std::fs::remove_file(deck_backup).unwrap();
+        std::fs::remove_file(log_backup).unwrap();
+        std::fs::remove_file(DECK_COPY).unwrap();
+        std::fs::remove_file(LOG_COPY).unwrap();
+    }
+
+    #[test]
+    fn test_recover() {
+        const DECK_COPY: &str = "tests/test_recover_copy.mnemo";
+        let _ = std::fs::remove_file(DECK_COPY);
+        let deck_path = Path::new(DECK_COPY);
+        // left over from a failed run.
+        for (backup, _) in list_backups(deck_path, "") {
+            std::fs::remove_file(backup).unwrap();
+        }
+        std::fs::copy("tests/test_parse_ok.mnemo", DECK_COPY).unwrap();
+        let original = Deck::read_from_file(deck_path, None).unwrap();
+        let backup = original.backup_deck().unwrap();
+        std::fs::remove_file(DECK_COPY).unwrap();
+
+        let backups = list_backups(deck_path, "");
+        assert_eq!(backups.len(), 1);
+        assert_eq!(backups[0].0, backup);
+        restore_backup(&backups[0].0, deck_path).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(DECK_COPY).unwrap(),
+            std::fs::read_to_string("tests/test_parse_ok.mnemo").unwrap()
+        );
+        let recovered = Deck::read_from_file(deck_path, None).unwrap();
+        assert_eq!(recovered.ids, original.ids);
+        for id in original.ids.iter() {
+            assert_eq!(
+                recovered.cards[id].to_string(),
+                original.cards[id].to_string()
+            );
+        }
+
+        std::fs::remove_file(backup).unwrap();
+        std::fs::remove_file(DECK_COPY).unwrap();
+    }
+
+    #[test]
+    fn test_read_only() {
+        // a directory stands in for a file that can't be written, since root can
+        // write to files whatever their permissions.
+        const DECK_COPY: &str = "tests/test_read_only_copy.mnemo";
+        const LOG_COPY: &str = "tests/test_read_only_copy.mnemo.log";
+        let _ = std::fs::remove_dir(LOG_COPY);
+        std::fs::copy("tests/test_parse_ok.mnemo", DECK_COPY).unwrap();
+        std::fs::create_dir(LOG_COPY).unwrap();
+
+        let mut d = Deck::read_from_file(Path::new(DECK_COPY), None).unwrap();
+        assert!(d.read_only);
+        assert_eq!(d.warnings.len(), 1);
+        assert!(d.warnings[0].starts_with(&format!("{} is read-only (", LOG_COPY)));
+        assert!(
+            d.warnings[0].ends_with("progress will not be saved. --log-dir keeps logs elsewhere")
+        );
+        // no panic, and nothing written.
+        d.make_due(1);
+        assert!(!d.save_log());
+        d.read_only = false;
+        assert!(!d.save_log());
+        assert!(Path::new(LOG_COPY).is_dir());
+
+        d.path = PathBuf::from(LOG_COPY);
+        assert_eq!(d.add_cards("Madrid | Spain | M |"), Err(DeckErr::ReadOnly));
+
+        std::fs::remove_file(DECK_COPY).unwrap();
+        std::fs::remove_dir(LOG_COPY).unwrap();
+    }
+
+    #[test]
+    fn test_seed_known() {
+        let mut d = Deck::read_from_file(Path::new("tests/test_tags.mnemo"), None).unwrap();
+        assert_eq!(d.seed_known(Some("nordic"), 1.0), 1);
+        assert_eq!(d.get_new(), vec![2, 3, 4]);
+
+        assert_eq!(d.seed_known(None, 2.0), 3);
+        assert!(d.get_new().is_empty());
+        assert!(d.get_due().is_empty());
+        assert_eq!(d.status[&1].days_left(), 1);
+        assert_eq!(d.status[&3].days_left(), 2);
+        assert_eq!(d.status[&3].factor, 2.0);
+        assert_eq!(d.seed_known(None, 1.0), 0);
+    }
+
+    #[test]
+    fn test_normalize_factors() {
+        let mut d = Deck::read_from_file(Path::new("tests/test_review_done.mnemo"), None).unwrap();
+        assert_eq!(d.factor_summary(), Some((8.0, 64.0, 128.0)));
+        assert_eq!(d.normalize_factors(16.0), 3);
+        assert_eq!(d.factor_summary(), Some((8.0, 16.0, 16.0)));
+        assert_eq!(d.status[&3].timestamp, 300000000);
+        // long overdue cards stay due.
+        assert_eq!(d.status[&1].timestamp, 100000000);
+
+        // far off due dates come within the cap.
+        d.status.get_mut(&4).unwrap().timestamp = i64::MAX / 2;
+        d.status.get_mut(&4).unwrap().factor = 100.0;
+        assert_eq!(d.normalize_factors(16.0), 1);
+        assert!(d.status[&4].days_left() <= 16);
+        assert_eq!(d.normalize_factors(16.0), 0);
+    }
+
+    #[test]
+    fn test_fix_timestamps() {
+        let mut d =
+            Deck::read_from_file(Path::new("tests/test_fix_timestamps.mnemo"), None).unwrap();
+        d.added.insert(3, Local::now().timestamp() - 86400);
+        assert_eq!(d.fix_timestamps(), vec![2, 3]);
+        assert_eq!(d.status[&1].timestamp, 1600000000);
+        assert_eq!(d.status[&2].days_left(), 8);
+        assert_eq!(d.status[&3].days_left(), 3);
+        assert!(d.fix_timestamps().is_empty());
+    }
+
+    #[test]
+    fn test_diff() {
+        const DECK_COPY: &str = "tests/test_diff_copy.mnemo";
+        std::fs::write(
+            DECK_COPY,
+            "0 | Capital | Country | First letter | Founded\n\
+             1 | Stockholm | Sweden | S | 1252\n\
+             3 | Washington, D.C. | USA | W | 1791\n\
+             4 | Antananarivo | Madagascar | A |\n\
+             5 | Mogadishu | Somalia | M |\n\
+             6 | Nairobi | Kenya | N |\n",
+        )
+        .unwrap();
+        let old = Deck::read_from_file(Path::new("tests/test_parse_ok.mnemo"), None).unwrap();
+        let new = Deck::read_from_file(Path::new(DECK_COPY), None).unwrap();
+        let diff = old.diff(&new);
+        assert_eq!(diff.added, vec![6]);
+        assert_eq!(diff.removed, vec![2]);
+        assert_eq!(
+            diff.changed,
+            vec![(
+                3,
+                "3 | Washington DC | USA | W | 1791".to_string(),
+                "3 | Washington, D.C. | USA | W | 1791".to_string()
+            )]
+        );
+
+        let mut out = vec![];
+        diff.write(&mut out).unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .starts_with("added: #6\nremoved: #2\nchanged: 1\n- 3 | Washington DC"));
+
+        std::fs::remove_file(DECK_COPY).unwrap();
+    }
+
+    #[test]
+    fn test_export_study_sheet() {
+        let d = Deck::read_from_file(Path::new("tests/test_parse_ok.mnemo"), None).unwrap();
+        let sheet = |hide_answers| {
+            let mut out = vec![];
+            d.export_study_sheet_section(&mut out, hide_answers)
+                .unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        let html = sheet(false);
+        assert!(html.starts_with("<section>\n<h1>tests/test_parse_ok.mnemo</h1>\n"));
+        assert!(html.ends_with("</section>\n"));
+        assert!(html.contains("<th>Country / First letter / Founded</th><th>Capital</th>"));
+        for id in d.ids.iter() {
+            let card = &d.cards[id];
+            assert!(html.contains(&card.cues[0]));
+            assert!(html.contains(&card.answer));
+        }
+        assert!(html.contains("<td>1</td><td>Sweden / S / 1252</td><td>Stockholm</td>"));
+        assert!(html.contains("<td>2</td><td>Norway / O</td><td>Oslo</td>"));
+        assert!(!html.contains("page-break"));
+
+        let html = sheet(true);
+        assert!(html.contains("<td>1</td><td>Sweden / S / 1252</td><td></td>"));
+        let answers = &html[html.find("page-break").unwrap()..];
+        assert!(answers.contains("<li>Stockholm</li>"));
+        assert!(!html[..html.find("page-break").unwrap()].contains("Stockholm"));
+
+        assert_eq!(escape_html("<a & \"b\">"), "&lt;a &amp; &quot;b&quot;&gt;");
+    }
+
+    #[test]
+    fn test_new() {
+        let mut d = Deck::new(Path::new("memory.mnemo"), 2);
+        let header = "0 | Capital | Country";
+        d.push_card(Card::from_str(header).unwrap(), 1, header)
+            .unwrap();
+        for (i, card) in [
+            "1 | Stockholm | Sweden",
+            "3 | Oslo | Norway",
+            "2 | Rome | Italy",
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            d.push_card(Card::from_str(card).unwrap(), i + 2, card)
+                .unwrap();
+        }
+        assert_eq!(d.header.as_ref().unwrap().answer, "Capital");
+        assert_eq!(d.ids, vec![1, 3, 2]);
+        assert_eq!(d.highest_id, 3);
+        assert_eq!(
+            d.push_card(
+                Card::from_str("4 | Paris | France | F").unwrap(),
+                5,
+                "4 | Paris | France | F"
+            ),
+            Err(DeckErr::InconsistentNumberOfFields {
+                id: 4,
+                line: 5,
+                content: "4 | Paris | France | F".to_string(),
+                size: 2,
+                expected_size: 1
+            })
+        );
+        assert_eq!(d.ids.len(), 3);
+
+        assert_eq!(d.get_new(), vec![1, 2, 3]);
+        d.set_status(3, Status::from_str("0,100000000,2.00").unwrap());
+        d.set_status(7, Status::from_str("7,100000000,2.00").unwrap());
+        assert_eq!(d.status[&3].id, 3);
+        assert_eq!(d.highest_id, 7);
+        assert_eq!(d.get_new(), vec![1, 2]);
+        assert_eq!(d.get_due(), vec![3]);
+    }
+
+    #[test]
+    fn test_export_stripped() {
+        let d = Deck::read_from_reader(
+            &mut "# learning_steps: 1,10\n\
+                  0 | Capital | Country\n\
+                  3 | Oslo | Norway | %private | @nordic\n\
+                  7 | Rome | Italy\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let mut out = vec![];
+        d.export_stripped(&mut out, false, false).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "0 | Capital | Country\n3 | Oslo | Norway\n7 | Rome | Italy\n"
+        );
+
+        let d = Deck::read_from_file(Path::new("tests/test_parse_ok.mnemo"), None).unwrap();
+        let mut out = vec![];
+        d.export_stripped(&mut out, true, false).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("0 | Capital | Country | First letter | Founded\n1 | Stockholm"));
+        assert!(out.contains("\n2 | Oslo | Norway | O | \n"));
+        assert!(!out.contains("100000000"));
+        let stripped = Deck::read_from_reader(&mut out.as_bytes()).unwrap();
+        assert_eq!(stripped.ids, vec![1, 2, 3, 4, 5]);
+        assert!(stripped.status.is_empty());
+
+        // founded is empty for some cards only, so it stays.
+        let mut trimmed = vec![];
+        d.export_stripped(&mut trimmed, true, true).unwrap();
+        assert_eq!(String::from_utf8(trimmed).unwrap(), out);
+
+        let d = Deck::read_from_reader(
+            &mut "0 | Capital | Country | Note | Extra\n\
+                  1 | Oslo | Norway | | \n\
+                  2 | Rome | Italy | it | \n\
+                  3 | Bern | | | \n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let mut out = vec![];
+        d.export_stripped(&mut out, false, true).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(
+            out,
+            "0 | Capital | Country | Note\n1 | Oslo | Norway | \n2 | Rome | Italy | it\n3 | Bern |  | \n"
+        );
+        assert!(Deck::read_from_reader(&mut out.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_pad_fields() {
+        let contents =
+            std::fs::read_to_string("tests/test_parse_inconsistent_number_of_fields.mnemo")
+                .unwrap();
+        let (padded, n) = pad_fields(&contents);
+        assert_eq!(n, 1);
+        assert!(padded.contains("\n3 | Washington DC | USA |  | \n"));
+        assert_eq!(padded.lines().count(), contents.lines().count());
+        let d = Deck::read_from_reader(&mut padded.as_bytes()).unwrap();
+        assert_eq!(d.cards[&3].cues, vec!["USA", "", ""]);
+        assert_eq!(pad_fields(&padded), (padded.clone(), 0));
+
+        let (padded, n) = pad_fields("# a comment\n1 | a | A | x\n2 | b | %note\n");
+        assert_eq!(n, 1);
+        assert_eq!(padded, "# a comment\n1 | a | A | x\n2 | b |  |  | %note\n");
+    }
+
+    #[test]
+    fn test_reformat() {
+        let messy = std::fs::read_to_string("tests/test_reformat.mnemo").unwrap();
+        let expected = std::fs::read_to_string("tests/test_reformat_expected.mnemo").unwrap();
+        let (aligned, n) = reformat(&messy, true);
+        assert_eq!(aligned, expected);
+        assert_eq!(n, 5);
+        assert_eq!(reformat(&aligned, true), (aligned.clone(), 0));
+
+        let (plain, _) = reformat(&messy, false);
+        assert!(plain.starts_with("# learning_steps: 1,10\n0 | Capital | Country | First letter"));
+        assert!(
+            plain.contains("\n2 | Oslo | Norway | O |\n3 | Washington DC | USA | W | 1791 | %the")
+        );
+
+        let cards = |contents: &str| {
+            let d = Deck::read_from_reader(&mut contents.as_bytes()).unwrap();
+            std::iter::once(d.header.as_ref().unwrap().to_string())
+                .chain(d.iter_cards().map(|(card, _)| card.to_string()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(cards(&aligned), cards(&messy));
+        assert_eq!(cards(&plain), cards(&messy));
+        // the fixtures are aligned already.
+        let ok = std::fs::read_to_string("tests/test_parse_ok.mnemo").unwrap();
+        assert_eq!(reformat(&ok, true), (ok.clone(), 0));
+
+        // wide characters take two columns each.
+        let (aligned, _) = reformat(
+            "1 | 東京 | Japan\n2 | Oslo | Norway\n3 | Rom | Italy\n",
+            true,
+        );
+        assert_eq!(
+            aligned,
+            "1 | 東京 | Japan\n2 | Oslo | Norway\n3 | Rom  | Italy\n"
+        );
+    }
+
+    #[test]
+    fn test_iter_cards() {
+        let d = Deck::read_from_file(Path::new("tests/test_parse_ok.mnemo"), None).unwrap();
+        let cards = d
+            .iter_cards()
+            .map(|(card, status)| (card.id, status.map(|s| s.timestamp)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            cards,
+            vec![
+                (1, Some(100000000)),
+                (2, Some(200000000)),
+                (3, Some(300000000)),
+                (4, None),
+                (5, None)
+            ]
+        );
+
+        let mut contents = "2 | b\n0 | header\n1 | a\n".as_bytes();
+        let d = Deck::read_from_reader(&mut contents).unwrap();
+        let ids = d.iter_cards().map(|(card, _)| card.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![2, 1]);
+    }
+
     #[test]
     fn test_get_new() {
-        let d = Deck::read_from_file(Path::new("tests/test_parse_ok.mnemo")).unwrap();
+        let d = Deck::read_from_file(Path::new("tests/test_parse_ok.mnemo"), None).unwrap();
         let new = d.get_new();
         assert_eq!(new.len(), 2);
         assert_eq!(d.cards[&new[0]].answer, "Antananarivo");
         assert_eq!(d.cards[&new[1]].answer, "Mogadishu");
     }
 
+    #[test]
+    fn test_dump_to_file() {
+        let d = Deck::read_from_file(Path::new("tests/test_parse_ok.mnemo"), None).unwrap();
+        let out = std::env::temp_dir().join("mnemo_test_dump_to_file.csv");
+        {
+            let mut f = File::create(&out).unwrap();
+            d.dump(&mut f, DEFAULT_DATE_FORMAT).unwrap();
+        }
+        let contents = std::fs::read_to_string(&out).unwrap();
+        let lines = contents.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with("1,Stockholm,"));
+        assert!(lines[0].ends_with(",1.00"));
+        assert!(lines[4].starts_with("5,Mogadishu,"));
+        assert!(lines[4].ends_with(",0.00"));
+        std::fs::remove_file(out).unwrap();
+    }
+
+    #[test]
+    fn test_keep_n() {
+        let mut d = Deck::read_from_file(Path::new("tests/test_parse_ok.mnemo"), None).unwrap();
+        d.keep_n(2, true);
+        let mut out = vec![];
+        d.dump(&mut out, DEFAULT_DATE_FORMAT).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("4,Antananarivo,"));
+        assert!(lines[1].starts_with("5,Mogadishu,"));
+
+        let mut d = Deck::read_from_file(Path::new("tests/test_parse_ok.mnemo"), None).unwrap();
+        d.keep_n(1, false);
+        assert_eq!(d.ids, vec![1]);
+        assert_eq!(d.get_new(), vec![]);
+    }
+
+    #[test]
+    fn test_renumber_orphans() {
+        // the log still has card 1, deleted since, which 2 is renumbered to.
+        let mut d =
+            Deck::read_from_file(Path::new("tests/test_renumber_orphan.mnemo"), None).unwrap();
+        d.added.insert(1, 5);
+        let files = d.renumber().unwrap();
+        assert_eq!(files[1].1, "1,200,2.00\n");
+        assert_eq!(d.status.keys().collect::<Vec<_>>(), vec![&1]);
+        assert!(d.added.is_empty());
+        assert_eq!(d.ids, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_preview_log() {
+        let mut d = Deck::read_from_file(Path::new("tests/test_parse_ok.mnemo"), None).unwrap();
+        let before = std::fs::read_to_string(&d.log_path).unwrap();
+        d.status.get_mut(&2).unwrap().factor = 4.0;
+
+        let mut out = vec![];
+        assert!(crate::diff::preview_file(&d.log_path, &d.log_contents(), &mut out).unwrap());
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("\n-2,200000000,2.00\n+2,200000000,4.00\n"));
+        assert!(!out.contains("1,100000000"));
+        assert_eq!(std::fs::read_to_string(&d.log_path).unwrap(), before);
+    }
+
+    #[test]
+    fn test_read_status_only() {
+        const DECK: &str = "tests/test_read_status_only_large.mnemo";
+        const LOG: &str = "tests/test_read_status_only_large.mnemo.log";
+        let n = 20_000;
+        let mut deck = String::from("# min_reviews_done: 1\n0 | answer | cue\n");
+        let mut log = String::new();
+        for id in 1..=n {
+            deck += &format!("{} | answer {} | cue {} | @tag\n", id, id, id);
+            // a third due, a third done, a third new.
+            match id % 3 {
+                0 => log += &format!("{},100000000,2.00\n", id),
+                1 => log += &format!("{},100000000,64.00,0,1\n", id),
+                _ => {}
+            }
+        }
+        std::fs::write(DECK, deck).unwrap();
+        std::fs::write(LOG, log).unwrap();
+
+        let full = Deck::read_from_file(Path::new(DECK), None).unwrap();
+        let lazy = Deck::read_status_only(Path::new(DECK), None).unwrap();
+
+        assert!(lazy.cards.is_empty());
+        assert_eq!(lazy.ids.len(), n);
+        assert_eq!(lazy.get_due().len(), full.get_due().len());
+        assert_eq!(lazy.get_done().len(), full.get_done().len());
+        assert_eq!(lazy.get_new(), full.get_new());
+        assert_eq!(lazy.get_due().len(), n / 3);
+
+        std::fs::remove_file(DECK).unwrap();
+        std::fs::remove_file(LOG).unwrap();
+
+        // fields are still checked.
+        let path = Path::new("tests/test_parse_inconsistent_number_of_fields.mnemo");
+        assert_eq!(
+            Deck::read_status_only(path, None).unwrap_err(),
+            Deck::read_from_file(path, None).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_get_new_priority() {
+        let d = Deck::read_from_file(Path::new("tests/test_priority.mnemo"), None).unwrap();
+        assert_eq!(d.get_new(), vec![3, 5, 1, 4, 2]);
+    }
+
+    #[test]
+    fn test_factor_precision() {
+        let mut d = Deck::read_from_reader(&mut "1 | a | A\n".as_bytes()).unwrap();
+        let status = Status {
+            timestamp: 100,
+            factor: 2.345678,
+            ..Status::new(1)
+        };
+        d.status.insert(1, status);
+        assert_eq!(d.log_contents(), "1,100,2.35\n");
+
+        let mut d =
+            Deck::read_from_reader(&mut "# factor_precision: 6\n1 | a | A\n".as_bytes()).unwrap();
+        d.status.insert(1, status);
+        assert_eq!(d.log_contents(), "1,100,2.345678\n");
+        assert_eq!(parse_log(&d.log_contents()).unwrap()[&1].factor, 2.345678);
+    }
+
+    #[test]
+    fn test_save_log_sorted() {
+        const DECK_COPY: &str = "tests/test_save_log_sorted.mnemo";
+        const LOG_COPY: &str = "tests/test_save_log_sorted.mnemo.log";
+        std::fs::write(DECK_COPY, "3 | c | C\n1 | a | A\n2 | b | B\n").unwrap();
+        std::fs::write(LOG_COPY, "2,100,1.00\n3,300,1.00\n1,200,1.00\n").unwrap();
+
+        let mut d = Deck::read_from_file(Path::new(DECK_COPY), None).unwrap();
+        assert_eq!(d.ids, vec![3, 1, 2]);
+        d.save_log();
+        assert_eq!(
+            std::fs::read_to_string(LOG_COPY).unwrap(),
+            "1,200,1.00\n2,100,1.00\n3,300,1.00\n"
+        );
+
+        d.config.sort_log = SortLog::Timestamp;
+        d.save_log();
+        assert_eq!(
+            std::fs::read_to_string(LOG_COPY).unwrap(),
+            "2,100,1.00\n1,200,1.00\n3,300,1.00\n"
+        );
+
+        d.config.sort_log = SortLog::File;
+        d.save_log();
+        assert_eq!(
+            std::fs::read_to_string(LOG_COPY).unwrap(),
+            "3,300,1.00\n1,200,1.00\n2,100,1.00\n"
+        );
+
+        std::fs::remove_file(DECK_COPY).unwrap();
+        std::fs::remove_file(LOG_COPY).unwrap();
+    }
+
     #[test]
     fn test_add() {
         const DECK_COPY: &str = "tests/test_parse_ok_copy.mnemo";
@@ -475,16 +3551,46 @@ mod test_deck {
         )
         .unwrap();
 
-        let d = Deck::read_from_file(Path::new(DECK_COPY)).unwrap();
+        let d = Deck::read_from_file(Path::new(DECK_COPY), None).unwrap();
         assert_eq!(d.highest_id, 10);
 
-        d.add_cards("Madrid | Spain | M |\nLisabon | Portugal | L |");
-        let d = Deck::read_from_file(Path::new(DECK_COPY)).unwrap();
+        d.add_cards("Madrid | Spain | M |\nLisabon | Portugal | L |")
+            .unwrap();
+        let d = Deck::read_from_file(Path::new(DECK_COPY), None).unwrap();
         assert_eq!(d.highest_id, 12);
         assert_eq!(d.cards[&11].answer, "Madrid");
         assert_eq!(d.cards[&12].cues[0], "Portugal");
 
+        // added dates
+        assert_eq!(d.added_date(11), Some(Local::now().date_naive()));
+        assert_eq!(d.added_date(12), Some(Local::now().date_naive()));
+        assert_eq!(d.added_date(1), None);
+        let mut out = vec![];
+        assert!(d.peek(12, &mut out, DEFAULT_DATE_FORMAT));
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("Capital: Lisabon"));
+        assert!(out.contains(&format!("added {}", Local::now().date_naive())));
+
+        // one bad card and nothing is added.
+        let before = std::fs::read_to_string(DECK_COPY).unwrap();
+        assert_eq!(
+            d.add_cards("Paris | France | P |\nRome | Italy\n"),
+            Err(DeckErr::InconsistentNumberOfFields {
+                id: 14,
+                line: 2,
+                content: "Rome | Italy".to_string(),
+                size: 1,
+                expected_size: 3
+            })
+        );
+        assert!(matches!(
+            d.add_cards("Paris | France | P |\n | Italy | I |\n"),
+            Err(DeckErr::BadCard { line: 2, .. })
+        ));
+        assert_eq!(std::fs::read_to_string(DECK_COPY).unwrap(), before);
+
         std::fs::remove_file(Path::new(DECK_COPY)).unwrap();
         std::fs::remove_file(Path::new(LOG_COPY)).unwrap();
+        std::fs::remove_file(Path::new("tests/test_parse_ok_copy.mnemo.added")).unwrap();
     }
 }
@@ -0,0 +1,147 @@
+use std::{
+    io::{BufRead, Write},
+    path::Path,
+};
+
+use colored::Colorize;
+
+// above this many cells the lcs table gets too big, and the diff falls back to
+// removing every old line and adding every new one.
+const MAX_TABLE: usize = 4_000_000;
+
+#[derive(Debug, PartialEq)]
+pub enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<Line<'a>> {
+    let old = old.lines().collect::<Vec<_>>();
+    let new = new.lines().collect::<Vec<_>>();
+
+    let prefix = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    let mut lines = old[..prefix]
+        .iter()
+        .map(|l| Line::Same(l))
+        .collect::<Vec<_>>();
+    if (a.len() + 1) * (b.len() + 1) > MAX_TABLE {
+        lines.extend(a.iter().map(|l| Line::Removed(l)));
+        lines.extend(b.iter().map(|l| Line::Added(l)));
+    } else {
+        // lcs[i][j] is the longest common subsequence of a[i..] and b[j..].
+        let w = b.len() + 1;
+        let mut lcs = vec![0u32; (a.len() + 1) * w];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lcs[i * w + j] = if a[i] == b[j] {
+                    lcs[(i + 1) * w + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * w + j].max(lcs[i * w + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                lines.push(Line::Same(a[i]));
+                i += 1;
+                j += 1;
+            } else if j == b.len() || (i < a.len() && lcs[(i + 1) * w + j] >= lcs[i * w + j + 1]) {
+                lines.push(Line::Removed(a[i]));
+                i += 1;
+            } else {
+                lines.push(Line::Added(b[j]));
+                j += 1;
+            }
+        }
+    }
+    lines.extend(old[old.len() - suffix..].iter().map(|l| Line::Same(l)));
+    lines
+}
+
+// prints the changed lines of a file in the style of a unified diff, without
+// context. returns whether anything changed.
+pub fn print_diff(path: &Path, old: &str, new: &str, out: &mut dyn Write) -> std::io::Result<bool> {
+    let lines = diff_lines(old, new);
+    if lines.iter().all(|line| matches!(line, Line::Same(_))) {
+        return Ok(false);
+    }
+    writeln!(out, "--- {}", path.to_string_lossy())?;
+    writeln!(out, "+++ {}", path.to_string_lossy())?;
+    for line in lines {
+        match line {
+            Line::Same(_) => {}
+            Line::Removed(l) => writeln!(out, "{}", format!("-{}", l).red())?,
+            Line::Added(l) => writeln!(out, "{}", format!("+{}", l).green())?,
+        }
+    }
+    Ok(true)
+}
+
+// prints what writing `new` to `path` would change, without writing anything. a
+// missing file counts as empty.
+pub fn preview_file(path: &Path, new: &str, out: &mut dyn Write) -> std::io::Result<bool> {
+    let old = std::fs::read_to_string(path).unwrap_or_default();
+    print_diff(path, &old, new, out)
+}
+
+// asks before rewriting files. anything but y, including eof, is a no.
+pub fn confirm(input: &mut dyn BufRead, out: &mut dyn Write) -> bool {
+    write!(out, "continue? [y/n] ").unwrap();
+    out.flush().unwrap();
+    let mut s = String::new();
+    input.read_line(&mut s).unwrap_or(0);
+    s.trim() == "y"
+}
+
+#[cfg(test)]
+mod test_diff {
+    use super::*;
+
+    #[test]
+    fn test_diff_lines() {
+        assert_eq!(
+            diff_lines("a\nb\nc\nd\n", "a\nc\nx\nd\n"),
+            vec![
+                Line::Same("a"),
+                Line::Removed("b"),
+                Line::Same("c"),
+                Line::Added("x"),
+                Line::Same("d"),
+            ]
+        );
+        assert_eq!(diff_lines("", "a\n"), vec![Line::Added("a")]);
+        assert_eq!(diff_lines("a\n", "a\n"), vec![Line::Same("a")]);
+    }
+
+    #[test]
+    fn test_print_diff() {
+        let mut out = vec![];
+        assert!(!print_diff(Path::new("x"), "a\n", "a\n", &mut out).unwrap());
+        assert!(out.is_empty());
+        assert!(print_diff(Path::new("x"), "a\nb\n", "a\nc\n", &mut out).unwrap());
+        assert_eq!(String::from_utf8(out).unwrap(), "--- x\n+++ x\n-b\n+c\n");
+    }
+
+    #[test]
+    fn test_confirm() {
+        let mut out = vec![];
+        assert!(confirm(&mut "y\n".as_bytes(), &mut out));
+        assert!(!confirm(&mut "n\n".as_bytes(), &mut out));
+        assert!(!confirm(&mut "".as_bytes(), &mut out));
+    }
+}
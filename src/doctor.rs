@@ -0,0 +1,128 @@
+use std::path::{Path, PathBuf};
+
+use colored::Colorize;
+
+use crate::{
+    audio::AudioCommand,
+    deck::{Deck, BACKUP_DIR},
+};
+
+pub struct Check {
+    pub name: String,
+    pub result: Result<(), String>,
+}
+
+pub fn run_checks(paths: &[PathBuf], log_dir: Option<&Path>, audio: &AudioCommand) -> Vec<Check> {
+    let program = audio.template.split_whitespace().next().unwrap_or("");
+    let mut checks = vec![
+        Check {
+            name: format!("backup directory {} is writable", BACKUP_DIR),
+            result: check_writable_dir(Path::new(BACKUP_DIR)),
+        },
+        Check {
+            name: format!("audio command {} is on PATH", program),
+            result: find_program(program)
+                .map(|_| ())
+                .ok_or_else(|| "not found, --speak-lang won't work".to_string()),
+        },
+    ];
+
+    for path in paths.iter() {
+        checks.push(Check {
+            name: format!("{} and its log parse", path.to_string_lossy()),
+            result: Deck::read_from_file(path, log_dir)
+                .map(|_| ())
+                .map_err(|err| format!("{:?}", err)),
+        });
+    }
+
+    checks
+}
+
+// prints the checklist and returns whether every check passed.
+pub fn print_checks(checks: &[Check]) -> bool {
+    for check in checks.iter() {
+        match &check.result {
+            Ok(()) => println!("[{}] {}", "ok".green(), check.name),
+            Err(err) => println!("[{}] {}: {}", "fail".red(), check.name, err),
+        }
+    }
+    checks.iter().all(|check| check.result.is_ok())
+}
+
+// a program given with a path is looked up as is, like a shell does.
+fn find_program(program: &str) -> Option<PathBuf> {
+    if program.is_empty() {
+        return None;
+    }
+    if program.contains('/') {
+        return Some(PathBuf::from(program)).filter(|path| path.is_file());
+    }
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|path| path.is_file())
+}
+
+// a missing directory is fine as long as the nearest existing ancestor is
+// writable, which is tried by creating and removing a file in it. permission bits
+// alone miss e.g. read-only mounts and ownership.
+fn check_writable_dir(dir: &Path) -> Result<(), String> {
+    let existing = dir
+        .ancestors()
+        .find(|p| p.exists())
+        .ok_or_else(|| "no existing parent directory".to_string())?;
+    if !existing.is_dir() {
+        return Err(format!("{} is not a directory", existing.to_string_lossy()));
+    }
+    let probe = existing.join(format!(".mnemo_doctor_{}", std::process::id()));
+    std::fs::File::create_new(&probe)
+        .and_then(|_| std::fs::remove_file(&probe))
+        .map_err(|err| format!("{} is not writable ({})", existing.to_string_lossy(), err))
+}
+
+#[cfg(test)]
+mod test_doctor {
+    use super::*;
+
+    #[test]
+    fn test_run_checks() {
+        let checks = run_checks(
+            &[
+                PathBuf::from("tests/test_parse_ok.mnemo"),
+                PathBuf::from("tests/test_parse_inconsistent_number_of_fields.mnemo"),
+                PathBuf::from("tests/does_not_exist.mnemo"),
+            ],
+            None,
+            &AudioCommand {
+                template: "sh -c {text}".to_string(),
+                ..AudioCommand::default()
+            },
+        );
+        assert_eq!(checks.len(), 5);
+        assert!(checks[1].result.is_ok());
+        assert!(checks[2].result.is_ok());
+        assert!(checks[3].result.is_err());
+        assert!(checks[4].result.is_err());
+    }
+
+    #[test]
+    fn test_find_program() {
+        assert!(find_program("sh").is_some());
+        assert_eq!(find_program("/bin/sh"), Some(PathBuf::from("/bin/sh")));
+        assert_eq!(find_program("mnemo_no_such_program"), None);
+        assert_eq!(find_program("tests/no_such_program"), None);
+        assert_eq!(find_program(""), None);
+    }
+
+    #[test]
+    fn test_check_writable_dir() {
+        assert!(check_writable_dir(Path::new("tests/no/such/dir")).is_ok());
+        // the probe file is gone again.
+        assert!(!std::fs::read_dir("tests").unwrap().any(|entry| entry
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .starts_with(".mnemo_doctor")));
+        assert!(check_writable_dir(Path::new("tests/test_parse_ok.mnemo")).is_err());
+    }
+}
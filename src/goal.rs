@@ -0,0 +1,64 @@
+use std::path::Path;
+
+use chrono::NaiveDate;
+use colored::Colorize;
+
+// reviews done on a given day, kept in a small state file as `date,reviews` so that
+// several sessions on the same day add up.
+pub fn reviews_on(path: &Path, date: NaiveDate) -> usize {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| {
+            let (day, reviews) = contents.trim().split_once(',')?;
+            (day.parse::<NaiveDate>().ok()? == date)
+                .then(|| reviews.parse().ok())
+                .flatten()
+        })
+        .unwrap_or(0)
+}
+
+pub fn add_reviews(path: &Path, date: NaiveDate, reviews: usize) -> std::io::Result<usize> {
+    let total = reviews_on(path, date) + reviews;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, format!("{},{}\n", date, total))?;
+    Ok(total)
+}
+
+pub fn progress(done: usize, goal: usize) -> String {
+    let line = format!("{}/{} done today.", done, goal);
+    if done >= goal {
+        format!("{} {}", line, "goal reached!".green())
+    } else {
+        line
+    }
+}
+
+#[cfg(test)]
+mod test_goal {
+    use super::*;
+
+    #[test]
+    fn test_goal() {
+        const STATE: &str = "tests/test_goal_today_copy";
+        let path = Path::new(STATE);
+        let _ = std::fs::remove_file(path);
+        let today = NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let tomorrow = today.succ_opt().unwrap();
+
+        assert_eq!(reviews_on(path, today), 0);
+        assert_eq!(progress(reviews_on(path, today), 40), "0/40 done today.");
+        assert_eq!(add_reviews(path, today, 28).unwrap(), 28);
+        assert_eq!(progress(reviews_on(path, today), 40), "28/40 done today.");
+        assert_eq!(add_reviews(path, today, 15).unwrap(), 43);
+        assert_eq!(
+            progress(reviews_on(path, today), 40),
+            "43/40 done today. goal reached!"
+        );
+        assert_eq!(reviews_on(path, tomorrow), 0);
+        assert_eq!(add_reviews(path, tomorrow, 5).unwrap(), 5);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,71 @@
+use std::str::FromStr;
+
+// the cards --ids picks: comma separated ids, ranges like `10-20` and bounds
+// like `>100`, e.g. `1-3,8,>100`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct IdSelector(Vec<IdRange>);
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum IdRange {
+    // inclusive on both ends.
+    Between(usize, usize),
+    Above(usize),
+}
+
+impl IdSelector {
+    pub fn contains(&self, id: usize) -> bool {
+        self.0.iter().any(|range| match *range {
+            IdRange::Between(low, high) => (low..=high).contains(&id),
+            IdRange::Above(low) => id > low,
+        })
+    }
+}
+
+impl FromStr for IdSelector {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_id = |id: &str| id.trim().parse::<usize>().map_err(|_| ());
+        s.split(',')
+            .map(|part| {
+                let part = part.trim();
+                if let Some(low) = part.strip_prefix('>') {
+                    Ok(IdRange::Above(parse_id(low)?))
+                } else if let Some((low, high)) = part.split_once('-') {
+                    let (low, high) = (parse_id(low)?, parse_id(high)?);
+                    if low > high {
+                        return Err(());
+                    }
+                    Ok(IdRange::Between(low, high))
+                } else {
+                    let id = parse_id(part)?;
+                    Ok(IdRange::Between(id, id))
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(IdSelector)
+    }
+}
+
+#[cfg(test)]
+mod test_ids {
+    use super::*;
+
+    fn selected(s: &str) -> Vec<usize> {
+        let selector = s.parse::<IdSelector>().unwrap();
+        (0..=12).filter(|&id| selector.contains(id)).collect()
+    }
+
+    #[test]
+    fn test_id_selector() {
+        assert_eq!(selected("3-6"), vec![3, 4, 5, 6]);
+        assert_eq!(selected("5,8, 11"), vec![5, 8, 11]);
+        assert_eq!(selected(">9"), vec![10, 11, 12]);
+        assert_eq!(selected("1-2,7,>11"), vec![1, 2, 7, 12]);
+        assert_eq!(selected("4-4"), vec![4]);
+
+        for invalid in ["", "20-10", "a-b", "3-", ">", "1,,2", "-5", "<3"] {
+            assert!(invalid.parse::<IdSelector>().is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,71 @@
+// converts other flashcard formats into lines for `Deck::add_cards`.
+
+#[derive(Debug, PartialEq)]
+pub enum ImportErr {
+    MissingAnswer { line: usize },
+}
+
+// mnemosyne's tab separated export: `question<tab>answer` per line. the question
+// becomes the cue. html formatting is dropped, and `|` would start a new field.
+pub fn from_mnemosyne(contents: &str) -> Result<String, ImportErr> {
+    let mut ret = String::new();
+    for (i, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (question, answer) = line
+            .split_once('\t')
+            .map(|(q, a)| (clean(q), clean(a)))
+            .filter(|(q, a)| !q.is_empty() && !a.is_empty())
+            .ok_or(ImportErr::MissingAnswer { line: i + 1 })?;
+        ret += &format!("{} | {}\n", answer, question);
+    }
+    Ok(ret)
+}
+
+fn clean(s: &str) -> String {
+    let s = s.replace("<br>", " ").replace("<br/>", " ");
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in s.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if in_tag => {}
+            '|' => text.push_str(" / "),
+            c => text.push(c),
+        }
+    }
+    let text = text
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&");
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod test_import {
+    use super::*;
+
+    #[test]
+    fn test_from_mnemosyne() {
+        let contents = std::fs::read_to_string("tests/test_import_mnemosyne.txt").unwrap();
+        assert_eq!(
+            from_mnemosyne(&contents).unwrap(),
+            "Stockholm | capital of <b>Sweden</b>\n\
+             Oslo | capital of Norway\n\
+             bread / loaf | pan\n\
+             R&D | research & development\n"
+        );
+        assert_eq!(
+            from_mnemosyne("a\tb\nno answer\n"),
+            Err(ImportErr::MissingAnswer { line: 2 })
+        );
+        assert_eq!(
+            from_mnemosyne("a\t<i></i>\n"),
+            Err(ImportErr::MissingAnswer { line: 1 })
+        );
+    }
+}
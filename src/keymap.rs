@@ -0,0 +1,136 @@
+use std::str::FromStr;
+
+// keys accepted when grading a card. `space` and `enter` stand for a lone
+// space and an empty line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyMap {
+    pub pass: String,
+    pub fail: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum KeyMapErr {
+    BadEntry(String),
+    UnknownAction(String),
+    Collision,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        KeyMap {
+            pass: "y".to_string(),
+            fail: "n".to_string(),
+        }
+    }
+}
+
+impl KeyMap {
+    // Some(correct) for a pass or fail key, None otherwise.
+    pub fn grade(&self, line: &str) -> Option<bool> {
+        let line = line.trim_end_matches(['\n', '\r']);
+        // only trim when that leaves a key, so a lone space stays a space.
+        let key = match line.trim() {
+            "" => line,
+            trimmed => trimmed,
+        }
+        .to_lowercase();
+        let key = match key.as_str() {
+            "" => "enter",
+            " " => "space",
+            key => key,
+        };
+        if key == self.pass {
+            Some(true)
+        } else if key == self.fail {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    pub fn prompt(&self) -> String {
+        format!("[{}/{}]", self.pass, self.fail)
+    }
+}
+
+// e.g. `pass=space,fail=f`. either key may be left out to keep its default.
+impl FromStr for KeyMap {
+    type Err = KeyMapErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut keymap = KeyMap::default();
+        for entry in s.split(',') {
+            let (action, key) = entry
+                .split_once('=')
+                .ok_or_else(|| KeyMapErr::BadEntry(entry.to_string()))?;
+            let key = key.trim().to_lowercase();
+            if key.is_empty() {
+                return Err(KeyMapErr::BadEntry(entry.to_string()));
+            }
+            match action.trim() {
+                "pass" => keymap.pass = key,
+                "fail" => keymap.fail = key,
+                action => return Err(KeyMapErr::UnknownAction(action.to_string())),
+            }
+        }
+        if keymap.pass == keymap.fail {
+            return Err(KeyMapErr::Collision);
+        }
+        Ok(keymap)
+    }
+}
+
+#[cfg(test)]
+mod test_keymap {
+    use super::*;
+
+    #[test]
+    fn test_fromstr() {
+        assert_eq!(KeyMap::from_str("pass=y,fail=n"), Ok(KeyMap::default()));
+        assert_eq!(
+            KeyMap::from_str("pass=space, fail=F"),
+            Ok(KeyMap {
+                pass: "space".to_string(),
+                fail: "f".to_string(),
+            })
+        );
+        assert_eq!(
+            KeyMap::from_str("fail=0").unwrap(),
+            KeyMap {
+                pass: "y".to_string(),
+                fail: "0".to_string(),
+            }
+        );
+        assert_eq!(KeyMap::from_str("pass=n"), Err(KeyMapErr::Collision));
+        assert_eq!(
+            KeyMap::from_str("skip=s"),
+            Err(KeyMapErr::UnknownAction("skip".to_string()))
+        );
+        assert!(matches!(
+            KeyMap::from_str("pass"),
+            Err(KeyMapErr::BadEntry(_))
+        ));
+        assert!(matches!(
+            KeyMap::from_str("pass="),
+            Err(KeyMapErr::BadEntry(_))
+        ));
+    }
+
+    #[test]
+    fn test_grade() {
+        let keymap = KeyMap::default();
+        assert_eq!(keymap.grade("y\n"), Some(true));
+        assert_eq!(keymap.grade(" N \n"), Some(false));
+        assert_eq!(keymap.grade("\n"), None);
+
+        let keymap = KeyMap::from_str("pass=space,fail=f").unwrap();
+        assert_eq!(keymap.grade(" \n"), Some(true));
+        assert_eq!(keymap.grade("f\n"), Some(false));
+        assert_eq!(keymap.grade("y\n"), None);
+
+        let keymap = KeyMap::from_str("pass=enter,fail=0").unwrap();
+        assert_eq!(keymap.grade("\n"), Some(true));
+        assert_eq!(keymap.grade("\r\n"), Some(true));
+        assert_eq!(keymap.grade("0\n"), Some(false));
+    }
+}
@@ -1,94 +1,1360 @@
-use std::{io::Read, path::PathBuf, process::exit};
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, ErrorKind, IsTerminal, Write},
+    path::PathBuf,
+    process::exit,
+};
 
 use argparse::ArgumentParser;
-use suite::{parse_files, Suite};
+use budget::NewBudget;
+use deck::{Deck, DeckErr};
+use ids::IdSelector;
+use scheduler::SchedulerKind;
+use suite::{
+    find_suite, parse_files, AccuracyThresholds, Bucket, DueOrder, PlayOptions, SortBy, Suite,
+};
 
+mod answer;
+mod audio;
+mod budget;
 mod card;
+mod config;
 mod deck;
+mod diff;
+mod doctor;
+mod goal;
+mod ids;
+mod import;
+mod keymap;
+mod scheduler;
 mod suite;
 
 #[derive(Debug)]
 struct Args {
     files: Vec<PathBuf>,
     randomize: bool,
-    max_new: usize,
+    jitter_order: Option<usize>,
+    priority: String,
+    minutes_per_deck: Option<f64>,
+    ahead: u32,
+    min_session: Option<usize>,
+    goal: Option<usize>,
+    rollover_new: Option<usize>,
+    playlist: Option<PathBuf>,
+    typed: bool,
+    scaffold: bool,
+    ease_step: Option<f64>,
+    accuracy_thresholds: Option<String>,
+    log_session: Option<PathBuf>,
+    audio_command: Option<String>,
+    audio_rate: Option<String>,
+    audio_voice: Option<String>,
+    add_interactive: bool,
+    write_next_due: Option<PathBuf>,
+    answers_file: Option<PathBuf>,
+    ascii: bool,
+    new_percent: Option<f64>,
+    warmup: usize,
+    new_every: Option<usize>,
+    review_done: usize,
+    confidence: bool,
+    unsure: bool,
+    autosave_every: Option<usize>,
+    speak_lang: Option<String>,
+    new_only: bool,
+    auto_advance: Option<f64>,
+    show_empty_cues: bool,
+    non_interactive: bool,
+    strip_parens: bool,
+    scheduler: Option<String>,
+    keys: Option<String>,
+    max_new: Option<usize>,
     max_old: Option<usize>,
     add_cards: Option<PathBuf>,
+    import_format: String,
     inspect: bool,
+    backup: bool,
+    validate: bool,
+    swap_thresholds: String,
+    list_done: bool,
+    count: Option<String>,
+    sort_by: Option<String>,
+    format: String,
     dump: bool,
+    first_n: Option<usize>,
+    last_n: Option<usize>,
+    ids: Option<String>,
+    export_log: bool,
+    study_sheet: bool,
+    strip: bool,
+    renumber: bool,
+    trim_empty_cues: bool,
+    hide_answers: bool,
+    import_log: Option<PathBuf>,
+    diff: Option<PathBuf>,
+    seed_known: bool,
+    replay: Option<PathBuf>,
+    normalize_factors: Option<f64>,
+    pad_fields: bool,
+    recover: bool,
+    reformat: bool,
+    align: bool,
+    recursive: bool,
+    due_now: Option<usize>,
+    fix_timestamps: bool,
+    seed_tag: Option<String>,
+    seed_factor: f64,
+    dry_run: bool,
+    yes: bool,
+    tags: bool,
+    stats: bool,
+    csv: bool,
+    peek: Option<usize>,
+    explain: Option<usize>,
     conceal_number: bool,
+    conceal_show_progress: bool,
+    interval_mod: Option<f64>,
+    tune: bool,
+    target_retention: f64,
+    quiet: bool,
+    relearn_gap: Option<usize>,
+    preview: bool,
+    pick: bool,
+    doctor: bool,
+    stdin: bool,
+    output: Option<PathBuf>,
+    date_format: String,
+    log_dir: Option<PathBuf>,
 }
 
 fn main() {
     let args = parse();
+    apply_ascii(&args);
 
-    if args.files.is_empty() {
+    if args.stdin {
+        if !args.files.is_empty() || args.add_cards.is_some() || args.doctor {
+            eprintln!("error: --stdin can't be combined with deck files, --add-cards or --doctor.");
+            exit(1);
+        }
+    } else if args.files.is_empty() && !args.doctor {
         eprintln!("no .mnemo files given. exiting.");
         exit(1);
     }
 
-    let paths = match parse_files(&args.files) {
+    // the playlist points into the decks by index, which picking would shift.
+    if args.pick && args.playlist.is_some() {
+        eprintln!("error: --pick can't be combined with --playlist.");
+        exit(1);
+    }
+    // the picker reads its answer from stdin, which --stdin takes the deck from.
+    if args.pick && args.stdin {
+        eprintln!("error: --pick can't be combined with --stdin.");
+        exit(1);
+    }
+
+    if args
+        .interval_mod
+        .is_some_and(|interval_mod| !(interval_mod > 0.0 && interval_mod.is_finite()))
+    {
+        eprintln!("error: --interval-mod must be a positive number.");
+        exit(1);
+    }
+    if !(args.target_retention > 0.0 && args.target_retention < 100.0) {
+        eprintln!("error: --target-retention must be between 0 and 100.");
+        exit(1);
+    }
+
+    let paths = match parse_files(&args.files, args.recursive) {
         Ok(paths) => paths,
         Err((p, err)) => {
             eprintln!("mnemo error:");
             eprintln!("{}: {:?}", p.to_string_lossy(), err);
+            if let Some(content) = err.content() {
+                eprintln!("    {}", content);
+            }
             eprintln!("exiting.");
             exit(1);
         }
     };
 
-    if args.add_cards.is_some() && paths.len() > 1 {
-        eprintln!("error: can only add cards to one file at a time.");
+    if !deck::validate_date_format(&args.date_format) {
+        eprintln!("error: invalid --date-format `{}`.", args.date_format);
         exit(1);
     }
 
-    let mut suite = match Suite::read_from_files(&paths) {
+    let log_dir = args
+        .log_dir
+        .clone()
+        .or_else(|| std::env::var_os("MNEMO_LOG_DIR").map(PathBuf::from));
+    if let Some(dir) = &log_dir {
+        if !dir.is_dir() {
+            eprintln!(
+                "error: log directory {} does not exist.",
+                dir.to_string_lossy()
+            );
+            exit(1);
+        }
+    }
+    let log_dir = log_dir.as_deref();
+
+    if args.doctor {
+        let mut audio = audio::AudioCommand::default();
+        if let Some(template) = &args.audio_command {
+            audio.template = template.clone();
+        }
+        let ok = doctor::print_checks(&doctor::run_checks(&paths, log_dir, &audio));
+        exit(if ok { 0 } else { 1 });
+    }
+
+    // the deck may well be gone, so this doesn't load it.
+    if args.recover {
+        for path in paths.iter() {
+            let backups = deck::list_backups(path, "");
+            if backups.is_empty() {
+                eprintln!(
+                    "no backups of {} in {}.",
+                    path.to_string_lossy(),
+                    deck::BACKUP_DIR
+                );
+                continue;
+            }
+            println!("backups of {}, newest first:", path.to_string_lossy());
+            for (n, (backup, timestamp)) in backups.iter().enumerate() {
+                let date = chrono::DateTime::from_timestamp(*timestamp, 0)
+                    .unwrap()
+                    .with_timezone(&chrono::Local)
+                    .format("%Y-%m-%d %H:%M:%S");
+                println!("{:>3}. {} ({})", n + 1, date, backup.to_string_lossy());
+            }
+            if args.dry_run {
+                continue;
+            }
+            let choice = if args.yes {
+                Some(1)
+            } else {
+                print!("restore which? [1] ");
+                std::io::stdout().flush().unwrap();
+                let mut line = String::new();
+                std::io::stdin().lock().read_line(&mut line).unwrap_or(0);
+                match line.trim() {
+                    "" => Some(1),
+                    n => n.parse::<usize>().ok(),
+                }
+            };
+            let Some((backup, _)) = choice
+                .and_then(|n| n.checked_sub(1))
+                .and_then(|i| backups.get(i))
+            else {
+                eprintln!("no such backup, skipping {}.", path.to_string_lossy());
+                continue;
+            };
+            if let Err(err) = deck::restore_backup(backup, path) {
+                eprintln!("could not restore {}: {}", path.to_string_lossy(), err);
+                exit(1);
+            }
+            eprintln!(
+                "restored {} from {}.",
+                path.to_string_lossy(),
+                backup.to_string_lossy()
+            );
+        }
+        exit(0);
+    }
+
+    if args.pad_fields && args.reformat {
+        eprintln!("error: --pad-fields and --reformat can't be combined.");
+        exit(1);
+    }
+    if args.align && !args.reformat {
+        eprintln!("error: --align only applies to --reformat.");
+        exit(1);
+    }
+    // decks with missing fields don't load, so this works on the files directly.
+    if args.pad_fields || args.reformat {
+        for path in paths.iter() {
+            let Ok(contents) = std::fs::read_to_string(path) else {
+                eprintln!("could not read {}.", path.to_string_lossy());
+                exit(1);
+            };
+            let (padded, n) = if args.pad_fields {
+                deck::pad_fields(&contents)
+            } else {
+                deck::reformat(&contents, args.align)
+            };
+            if n == 0 {
+                continue;
+            }
+            if !args.yes || args.dry_run {
+                let mut stdout = std::io::stdout();
+                diff::print_diff(path, &contents, &padded, &mut stdout).unwrap();
+                if args.dry_run || !diff::confirm(&mut std::io::stdin().lock(), &mut stdout) {
+                    continue;
+                }
+            }
+            if deck::backup_file(path, path, "").is_none() {
+                eprintln!("skipping {}.", path.to_string_lossy());
+                continue;
+            }
+            if let Err(err) = std::fs::write(path, padded) {
+                eprintln!(
+                    "could not write {}: {}. skipping it.",
+                    path.to_string_lossy(),
+                    err
+                );
+                continue;
+            }
+            if args.pad_fields {
+                eprintln!("padded {} cards in {}.", n, path.to_string_lossy());
+            } else {
+                eprintln!("reformatted {} lines in {}.", n, path.to_string_lossy());
+            }
+        }
+        exit(0);
+    }
+
+    if args.add_cards.is_some() {
+        // a suite names no single deck, even when it lists only one.
+        if let Some(suite) = find_suite(&args.files) {
+            eprintln!(
+                "error: {} is a suite. name the .mnemo file to add cards to instead.",
+                suite.to_string_lossy()
+            );
+            exit(1);
+        }
+    }
+    if (args.add_cards.is_some()
+        || args.import_log.is_some()
+        || args.diff.is_some()
+        || args.replay.is_some())
+        && paths.len() > 1
+    {
+        eprintln!("error: can only add cards, import a log, diff or replay one file at a time.");
+        exit(1);
+    }
+
+    let suite = if args.stdin {
+        Deck::read_from_reader(&mut std::io::stdin())
+            .map(|deck| Suite { decks: vec![deck] })
+            .map_err(|err| (PathBuf::from("<stdin>"), err))
+    } else if args.inspect || args.stats {
+        paths
+            .iter()
+            .map(|path| Deck::read_status_only(path, log_dir).map_err(|err| (path.clone(), err)))
+            .collect::<Result<_, _>>()
+            .map(|decks| Suite { decks })
+    } else {
+        Suite::read_from_files(&paths, log_dir)
+    };
+    let mut suite = match suite {
         Ok(suite) => suite,
         Err((p, err)) => {
             eprintln!("mnemo error:");
             eprintln!("{}: {:?}", p.to_string_lossy(), err);
+            if let Some(content) = err.content() {
+                eprintln!("    {}", content);
+            }
             eprintln!("exiting.");
             exit(1);
         }
     };
 
+    for deck in suite.decks.iter() {
+        for warning in deck.warnings.iter() {
+            eprintln!("warning: {}: {}", deck.path.to_string_lossy(), warning);
+        }
+    }
+
+    if args.strip_parens {
+        for deck in suite.decks.iter_mut() {
+            deck.config.strip_parens = true;
+        }
+    }
+    if let Some(name) = &args.scheduler {
+        let Ok(kind) = name.parse::<SchedulerKind>() else {
+            eprintln!("error: --scheduler must be doubling or leitner.");
+            exit(1);
+        };
+        let scheduler = kind.scheduler();
+        for deck in suite.decks.iter_mut() {
+            deck.config.scheduler = scheduler.clone();
+        }
+    }
+
+    // flags override the decks' own defaults.
+    for deck in suite.decks.iter_mut() {
+        if args.max_new.is_some() {
+            deck.config.opts.new_cards = None;
+        }
+        if args.max_old.is_some() {
+            deck.config.opts.max_old = None;
+        }
+    }
+
+    if let Some(interval_mod) = args.interval_mod {
+        for deck in suite.decks.iter_mut() {
+            deck.config.interval_mod = interval_mod;
+        }
+    }
+
+    if args.csv && !args.stats {
+        eprintln!("error: --csv only applies to --stats.");
+        exit(1);
+    }
+
+    if args.first_n.is_some() || args.last_n.is_some() {
+        if !(args.dump || args.inspect) {
+            eprintln!("error: --first-n and --last-n only apply to --dump and --inspect.");
+            exit(1);
+        }
+        for deck in suite.decks.iter_mut() {
+            match (args.first_n, args.last_n) {
+                (Some(k), None) => deck.keep_n(k, false),
+                (None, Some(k)) => deck.keep_n(k, true),
+                _ => {
+                    eprintln!("error: --first-n and --last-n can't be combined.");
+                    exit(1);
+                }
+            }
+        }
+    }
+
+    if let Some(ids) = &args.ids {
+        if !(args.dump || args.inspect || args.strip || args.study_sheet) {
+            eprintln!("error: --ids only applies to --dump, --inspect, --strip and --study-sheet.");
+            exit(1);
+        }
+        let Ok(selector) = ids.parse::<IdSelector>() else {
+            eprintln!("error: --ids takes ids and ranges like 10-20,25,>100.");
+            exit(1);
+        };
+        for deck in suite.decks.iter_mut() {
+            deck.keep_ids(|id| selector.contains(id));
+        }
+    }
+
     if args.dump {
-        for deck in suite.decks.into_iter() {
-            deck.dump();
+        write_output(&args.output, "dump", |out| {
+            suite
+                .decks
+                .iter()
+                .try_for_each(|deck| deck.dump(out, &args.date_format))
+        });
+    } else if args.stats && args.csv {
+        let today = chrono::Local::now().date_naive();
+        write_output(&args.output, "stats", |out| {
+            suite.decks.iter().try_for_each(|deck| {
+                deck.stats(deck::FORECAST_DAYS)
+                    .write_csv(out, today, &deck.path.to_string_lossy())
+            })
+        });
+    } else if args.stats {
+        for deck in suite.decks.iter() {
+            let stats = deck.stats(deck::FORECAST_DAYS);
+            println!(
+                "{}: {} new, {} due, {} done, {} total",
+                deck.path.to_string_lossy(),
+                stats.new,
+                stats.due,
+                stats.done,
+                stats.total
+            );
+            println!(
+                "  due this week: {}",
+                stats
+                    .forecast
+                    .iter()
+                    .map(|count| count.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            );
+        }
+    } else if args.strip {
+        // the original deck is never rewritten.
+        let overwrites = args
+            .output
+            .as_ref()
+            .and_then(|output| output.canonicalize().ok())
+            .is_some_and(|output| {
+                suite.decks.iter().any(|deck| {
+                    [&deck.path, &deck.log_path]
+                        .iter()
+                        .any(|path| path.canonicalize().is_ok_and(|path| path == output))
+                })
+            });
+        if overwrites {
+            eprintln!("error: --strip can't write over a deck or its log.");
+            exit(1);
+        }
+        write_output(&args.output, "stripped deck", |out| {
+            suite
+                .decks
+                .iter()
+                .try_for_each(|deck| deck.export_stripped(out, args.renumber, args.trim_empty_cues))
+        });
+    } else if args.renumber {
+        let mut rewrites = vec![];
+        for deck in suite.decks.iter_mut() {
+            if deck.read_only {
+                eprintln!(
+                    "error: {} has no file to renumber.",
+                    deck.path.to_string_lossy()
+                );
+                exit(1);
+            }
+            match deck.renumber() {
+                Ok(files) => rewrites.push((&*deck, files)),
+                Err(err) => {
+                    eprintln!("could not read {}: {}", deck.path.to_string_lossy(), err);
+                    exit(1);
+                }
+            }
+        }
+        let files = rewrites
+            .iter()
+            .flat_map(|(_, files)| files.iter().cloned())
+            .collect::<Vec<_>>();
+        if !confirm_files(
+            &files,
+            args.dry_run,
+            args.yes,
+            &mut std::io::stdin().lock(),
+            &mut std::io::stdout(),
+        ) {
+            exit(0);
+        }
+        for (deck, files) in rewrites {
+            // the deck goes first, so a deck that can't be rewritten keeps its log too.
+            if deck.backup_deck().is_none() {
+                eprintln!("skipping {}.", deck.path.to_string_lossy());
+                continue;
+            }
+            let written = files.iter().all(|(path, contents)| {
+                if *path == deck.log_path {
+                    deck.backup_log();
+                } else if *path != deck.path {
+                    // the sidecars are named after the deck.
+                    let deck_path = deck.path.to_string_lossy();
+                    let suffix = path.to_string_lossy().replacen(&*deck_path, "", 1);
+                    deck::backup_file(path, &deck.path, &suffix);
+                }
+                let written = std::fs::write(path, contents);
+                if let Err(err) = &written {
+                    eprintln!("could not write {}: {}", path.to_string_lossy(), err);
+                }
+                written.is_ok()
+            });
+            if written {
+                eprintln!("renumbered {}.", deck.path.to_string_lossy());
+            }
+        }
+    } else if args.study_sheet {
+        write_output(&args.output, "study sheet", |out| {
+            suite.export_study_sheet(out, args.hide_answers)
+        });
+    } else if args.export_log {
+        write_output(&args.output, "log", |out| {
+            suite.decks.iter().try_for_each(|deck| deck.export_log(out))
+        });
+    } else if let Some(import_file) = &args.import_log {
+        let incoming = std::fs::read_to_string(import_file)
+            .map_err(|_| DeckErr::FileNotFound)
+            .and_then(|contents| deck::parse_log(&contents));
+        match incoming {
+            Ok(incoming) => {
+                let deck = &mut suite.decks[0];
+                deck.status = Deck::merge_logs(&deck.status, &incoming);
+                if !confirm_rewrite(&[deck], args.dry_run, args.yes) {
+                    exit(0);
+                }
+                deck.backup_log();
+                deck.save_log();
+                eprintln!(
+                    "merged {} into {}.",
+                    import_file.to_string_lossy(),
+                    deck.log_path.to_string_lossy()
+                );
+            }
+            Err(err) => {
+                eprintln!("{}: {:?}", import_file.to_string_lossy(), err);
+                exit(1);
+            }
+        }
+    } else if let Some(other_file) = &args.diff {
+        let other = match Deck::read_from_file(other_file, None) {
+            Ok(other) => other,
+            Err(err) => {
+                eprintln!("{}: {:?}", other_file.to_string_lossy(), err);
+                exit(1);
+            }
+        };
+        suite.decks[0]
+            .diff(&other)
+            .write(&mut std::io::stdout())
+            .unwrap();
+    } else if let Some(id) = args.due_now {
+        let mut found = false;
+        for deck in suite.decks.iter_mut() {
+            found |= deck.make_due(id);
+        }
+        if !found {
+            eprintln!("no card with id {}.", id);
+            exit(1);
+        }
+        if !confirm_rewrite(
+            &suite.decks.iter().collect::<Vec<_>>(),
+            args.dry_run,
+            args.yes,
+        ) {
+            exit(0);
+        }
+        for deck in suite.decks.iter() {
+            deck.backup_log();
+            deck.save_log();
+        }
+    } else if let Some(path) = &args.replay {
+        let Ok(history) = std::fs::read_to_string(path) else {
+            eprintln!("could not read {}.", path.to_string_lossy());
+            exit(1);
+        };
+        let deck = &mut suite.decks[0];
+        // sessions back the log up as they start, so the newest of those backups
+        // is the log as it was before the last one.
+        let Some((backup, since)) = deck::list_backups(&deck.path, deck::SESSION_LOG_SUFFIX)
+            .into_iter()
+            .next()
+        else {
+            eprintln!(
+                "error: no session log backup of {} in {} to replay onto.",
+                deck.path.to_string_lossy(),
+                deck::BACKUP_DIR
+            );
+            exit(1);
+        };
+        let status = match std::fs::read_to_string(&backup).map(|log| deck::parse_log(&log)) {
+            Ok(Ok(status)) => status,
+            _ => {
+                eprintln!("error: could not read {}.", backup.to_string_lossy());
+                exit(1);
+            }
+        };
+        deck.status.clear();
+        for (id, status) in status {
+            deck.set_status(id, status);
+        }
+        let (skipped, earlier) = deck.replay(&history, since);
+        for line in skipped {
+            eprintln!(
+                "warning: {}: line {}: skipped.",
+                path.to_string_lossy(),
+                line
+            );
+        }
+        if earlier > 0 {
+            let date = chrono::DateTime::from_timestamp(since, 0)
+                .unwrap()
+                .with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S");
+            eprintln!(
+                "note: {}: left out {} answers from before the last session, at {}.",
+                path.to_string_lossy(),
+                earlier,
+                date
+            );
+        }
+        print!("{}", deck.log_contents());
+    } else if let Some(cap) = args.normalize_factors {
+        if !(cap >= 1.0 && cap.is_finite()) {
+            eprintln!("error: --normalize-factors must be at least 1.");
+            exit(1);
+        }
+        let summary = |deck: &Deck| match deck.factor_summary() {
+            Some((min, median, max)) => {
+                format!("min {:.2}, median {:.2}, max {:.2}", min, median, max)
+            }
+            None => "no learned cards".to_string(),
+        };
+        let mut changed = 0;
+        for deck in suite.decks.iter_mut() {
+            let before = summary(deck);
+            changed += deck.normalize_factors(cap);
+            eprintln!(
+                "{}: factors before: {}.",
+                deck.path.to_string_lossy(),
+                before
+            );
+            eprintln!(
+                "{}: factors after: {}.",
+                deck.path.to_string_lossy(),
+                summary(deck)
+            );
+        }
+        if changed == 0 {
+            eprintln!("no factors to normalize.");
+            exit(0);
+        }
+        if !confirm_rewrite(
+            &suite.decks.iter().collect::<Vec<_>>(),
+            args.dry_run,
+            args.yes,
+        ) {
+            exit(0);
+        }
+        for deck in suite.decks.iter() {
+            deck.backup_log();
+            deck.save_log();
+        }
+        eprintln!("normalized {} factors.", changed);
+    } else if args.seed_known {
+        if args.seed_factor < 1.0 {
+            eprintln!("error: --seed-factor must be at least 1.");
+            exit(1);
+        }
+        let tag = args.seed_tag.as_deref();
+        let seeded = suite
+            .decks
+            .iter_mut()
+            .map(|deck| deck.seed_known(tag, args.seed_factor))
+            .collect::<Vec<_>>();
+        if !confirm_rewrite(
+            &suite.decks.iter().collect::<Vec<_>>(),
+            args.dry_run,
+            args.yes,
+        ) {
+            exit(0);
+        }
+        // read-only decks and logs that failed to save seeded nothing.
+        let mut saved = None;
+        for (deck, seeded) in suite.decks.iter().zip(seeded) {
+            deck.backup_log();
+            if deck.save_log() {
+                *saved.get_or_insert(0) += seeded;
+            }
+        }
+        if let Some(saved) = saved {
+            eprintln!("seeded {} cards.", saved);
+        }
+    } else if args.fix_timestamps {
+        let mut fixed = 0;
+        for deck in suite.decks.iter_mut() {
+            for id in deck.fix_timestamps() {
+                eprintln!("{}: fixed #{}.", deck.path.to_string_lossy(), id);
+                fixed += 1;
+            }
+        }
+        if fixed == 0 {
+            eprintln!("no timestamps to fix.");
+            exit(0);
+        }
+        if !confirm_rewrite(
+            &suite.decks.iter().collect::<Vec<_>>(),
+            args.dry_run,
+            args.yes,
+        ) {
+            exit(0);
+        }
+        for deck in suite.decks.iter() {
+            deck.backup_log();
+            deck.save_log();
+        }
+    } else if let Some(path) = &args.write_next_due {
+        if let Err(err) = suite.write_next_due(path) {
+            eprintln!("error: could not write {}: {}", path.to_string_lossy(), err);
+            exit(1);
+        }
+    } else if let Some(id) = args.peek {
+        let mut found = false;
+        for deck in suite.decks.iter() {
+            found |= deck.peek(id, &mut std::io::stdout(), &args.date_format);
+        }
+        if !found {
+            eprintln!("no card with id {}.", id);
+            exit(1);
+        }
+    } else if args.tune {
+        let target = args.target_retention / 100.0;
+        let Some((retention, reviews)) = suite.retention() else {
+            eprintln!("no reviews logged yet. exiting.");
+            exit(1);
+        };
+        println!(
+            "retention: {:.1}% over {} reviews (target {:.1}%).",
+            retention * 100.0,
+            reviews,
+            target * 100.0
+        );
+        println!(
+            "suggested --interval-mod: {:.2}",
+            config::suggest_interval_mod(retention, target, args.interval_mod.unwrap_or(1.0))
+        );
+    } else if args.unsure {
+        for deck in suite.decks.iter() {
+            for (id, count) in deck.unsure_cards() {
+                println!(
+                    "{}::#{}: {} ({}x unsure)",
+                    deck.path.to_string_lossy(),
+                    id,
+                    deck.cards[&id].answer,
+                    count
+                );
+            }
+        }
+    } else if args.backup {
+        for deck in suite.decks.iter() {
+            for (path, backup) in [
+                (&deck.path, deck.backup_deck()),
+                (&deck.log_path, deck.backup_log()),
+            ] {
+                if let Some(backup) = backup {
+                    println!("{} -> {}", path.to_string_lossy(), backup.to_string_lossy());
+                }
+            }
+        }
+    } else if args.validate {
+        let Ok(thresholds) = args.swap_thresholds.parse() else {
+            eprintln!("error: --swap-thresholds must be a ratio of at least 1 and a number of words, e.g. 4,4.");
+            exit(1);
+        };
+        for deck in suite.decks.iter() {
+            for id in deck.possible_swaps(thresholds) {
+                println!(
+                    "{}::#{}: answer and cue swapped? {}",
+                    deck.path.to_string_lossy(),
+                    id,
+                    deck.cards[&id]
+                );
+            }
+        }
+    } else if let Some(bucket) = &args.count {
+        let Ok(bucket) = bucket.parse::<Bucket>() else {
+            eprintln!("error: --count must be due, new, done or total.");
+            exit(1);
+        };
+        println!("{}", suite.count(bucket));
+    } else if args.list_done {
+        for (deck_index, id, graduated) in suite.list_done() {
+            let deck = &suite.decks[deck_index];
+            let date = chrono::DateTime::from_timestamp(graduated, 0)
+                .unwrap()
+                .with_timezone(&chrono::Local)
+                .date_naive();
+            println!(
+                "{}::#{}: {} (done {})",
+                deck.path.to_string_lossy(),
+                id,
+                deck.cards[&id].answer,
+                deck::format_date(date, &args.date_format)
+            );
+        }
+    } else if args.tags {
+        for (tag, count) in suite.tag_counts() {
+            println!("{}: {}", tag, count);
         }
     } else if args.inspect {
-        for deck in suite.decks.into_iter() {
-            deck.inspect()
+        let sort_by = match args.sort_by.as_deref() {
+            None => None,
+            Some("due") => Some(SortBy::Due),
+            Some("new") => Some(SortBy::New),
+            Some("total") => Some(SortBy::Total),
+            Some(_) => {
+                eprintln!("error: --sort-by must be due, new or total.");
+                exit(1);
+            }
+        };
+        match (args.format.as_str(), sort_by) {
+            ("default", Some(sort_by)) => print!("{}", suite.inspect_table(sort_by)),
+            ("default", None) => {
+                for deck in suite.decks.into_iter() {
+                    deck.inspect()
+                }
+            }
+            ("minimal", _) => {
+                let (due, new) = suite.due_and_new();
+                println!("{}", suite::format_minimal((due, new)));
+                exit(if due > 0 { 0 } else { 1 });
+            }
+            _ => {
+                eprintln!("error: --format must be default or minimal.");
+                exit(1);
+            }
         }
     } else if let Some(add_cards_file) = args.add_cards {
         let cards = if add_cards_file.to_string_lossy() == "-" {
-            let mut s = String::new();
-            std::io::stdin().read_to_string(&mut s).unwrap();
-            s
+            let stdin = std::io::stdin();
+            let is_terminal = stdin.is_terminal();
+            deck::read_new_cards(&mut stdin.lock(), &mut std::io::stdout(), is_terminal)
         } else {
             std::fs::read_to_string(add_cards_file).unwrap()
         };
-        suite.decks[0].add_cards(&cards);
+        let cards = match args.import_format.as_str() {
+            "mnemo" => cards,
+            "mnemosyne" => match import::from_mnemosyne(&cards) {
+                Ok(cards) => cards,
+                Err(err) => {
+                    eprintln!("error: {:?}", err);
+                    exit(1);
+                }
+            },
+            _ => {
+                eprintln!("error: --import-format must be mnemo or mnemosyne.");
+                exit(1);
+            }
+        };
+        // without a terminal to ask on, short cards are rejected as usual.
+        let cards = if args.add_interactive && std::io::stdin().is_terminal() {
+            suite.decks[0].fill_missing_fields(
+                &cards,
+                &mut std::io::stdin().lock(),
+                &mut std::io::stdout(),
+            )
+        } else {
+            cards
+        };
+        match suite.decks[0].add_cards(&cards) {
+            Ok(()) => {}
+            Err(DeckErr::ReadOnly) => {
+                eprintln!(
+                    "error: {} is read-only.",
+                    suite.decks[0].path.to_string_lossy()
+                );
+                exit(1);
+            }
+            Err(err) => {
+                eprintln!("error: {:?}", err);
+                exit(1);
+            }
+        }
     } else {
-        suite.play(
-            args.max_new,
-            args.max_old,
-            args.randomize,
-            args.conceal_number,
-        );
+        let keys = match args.keys.as_deref().map(str::parse).transpose() {
+            Ok(keys) => keys.unwrap_or_default(),
+            Err(err) => {
+                eprintln!("error: invalid --keys: {:?}", err);
+                exit(1);
+            }
+        };
+        let playlist = args.playlist.as_ref().map(|path| {
+            let Ok(contents) = std::fs::read_to_string(path) else {
+                eprintln!("could not read playlist {}.", path.to_string_lossy());
+                exit(1);
+            };
+            let dir = path.parent().unwrap_or_else(|| std::path::Path::new(""));
+            let (playlist, warnings) = suite.parse_playlist(&contents, dir);
+            for warning in warnings {
+                eprintln!("warning: {}: {}", path.to_string_lossy(), warning);
+            }
+            playlist
+        });
+        if args
+            .auto_advance
+            .is_some_and(|secs| !(secs >= 0.0 && secs.is_finite()))
+        {
+            eprintln!("error: --auto-advance must be a number of seconds.");
+            exit(1);
+        }
+        if args
+            .new_percent
+            .is_some_and(|p| !(0.0..=100.0).contains(&p))
+        {
+            eprintln!("error: --new-percent must be between 0 and 100.");
+            exit(1);
+        }
+        let mut audio = audio::AudioCommand {
+            rate: args.audio_rate,
+            voice: args.audio_voice,
+            ..audio::AudioCommand::default()
+        };
+        if let Some(template) = args.audio_command {
+            audio.template = template;
+        }
+        if let Err(err) = audio.validate() {
+            eprintln!("error: invalid --audio-command: {:?}", err);
+            exit(1);
+        }
+        if let Some(path) = &args.log_session {
+            if let Err(err) = File::options().append(true).create(true).open(path) {
+                eprintln!("error: could not open {}: {}", path.to_string_lossy(), err);
+                exit(1);
+            }
+        }
+        let accuracy_thresholds = match args.accuracy_thresholds.as_deref().map(str::parse) {
+            None => AccuracyThresholds::default(),
+            Some(Ok(thresholds)) => thresholds,
+            Some(Err(())) => {
+                eprintln!(
+                    "error: --accuracy-thresholds must be two percentages, low first, e.g. 80,95."
+                );
+                exit(1);
+            }
+        };
+        if args
+            .ease_step
+            .is_some_and(|step| !(step > 0.0 && step.is_finite()))
+        {
+            eprintln!("error: --ease-step must be a positive number.");
+            exit(1);
+        }
+        if args
+            .minutes_per_deck
+            .is_some_and(|minutes| !(minutes > 0.0 && minutes.is_finite()))
+        {
+            eprintln!("error: --limit-session-by-minutes-per-deck must be a positive number.");
+            exit(1);
+        }
+        let due_order = match args.priority.as_str() {
+            "oldest" => DueOrder::Oldest,
+            "overdue" => DueOrder::Overdue,
+            _ => {
+                eprintln!("error: --priority must be oldest or overdue.");
+                exit(1);
+            }
+        };
+        let mut opts = PlayOptions {
+            max_new: args.max_new.unwrap_or(10),
+            max_old: args.max_old,
+            randomize: args.randomize,
+            conceal_number: args.conceal_number || args.conceal_show_progress,
+            conceal_progress: args.conceal_show_progress,
+            quiet: args.quiet,
+            relearn_gap: args.relearn_gap,
+            preview: args.preview,
+            jitter_order: args.jitter_order,
+            minutes_per_deck: args.minutes_per_deck,
+            ahead: args.ahead,
+            keys,
+            min_session: args.min_session,
+            playlist,
+            typed: args.typed,
+            scaffold: args.scaffold,
+            new_percent: args.new_percent,
+            warmup: args.warmup,
+            new_every: args.new_every,
+            review_done: args.review_done,
+            confidence: args.confidence,
+            record_history: true,
+            autosave_every: args.autosave_every.filter(|&n| n > 0),
+            speak_lang: args.speak_lang,
+            transcript: args.log_session,
+            audio,
+            new_only: args.new_only,
+            auto_advance: args.auto_advance,
+            show_empty_cues: args.show_empty_cues,
+            // with --stdin, answers come from /dev/tty.
+            non_interactive: args.non_interactive || args.stdin,
+            due_order,
+            accuracy_thresholds,
+            ease_step: args.ease_step,
+        };
+        if let Some(id) = args.explain {
+            if !suite.explain(id, &opts, &mut std::io::stdout(), &args.date_format) {
+                eprintln!("no card with id {}.", id);
+                exit(1);
+            }
+            exit(0);
+        }
+        if let Some(path) = &args.answers_file {
+            let Ok(answers) = std::fs::read_to_string(path) else {
+                eprintln!("could not read answers file {}.", path.to_string_lossy());
+                exit(1);
+            };
+            suite.grade_answers(&opts, &answers, &mut std::io::stdout());
+            exit(0);
+        }
+        if args.pick {
+            suite.pick(&mut std::io::stdin().lock(), &mut std::io::stdout());
+        }
+        // stdin is taken by the deck itself, so answers are read from the terminal.
+        let mut input: Box<dyn std::io::BufRead> = if args.stdin {
+            match File::open("/dev/tty") {
+                Ok(tty) => Box::new(BufReader::new(tty)),
+                Err(_) => {
+                    eprintln!("error: --stdin needs a terminal to read answers from.");
+                    exit(1);
+                }
+            }
+        } else {
+            Box::new(std::io::stdin().lock())
+        };
+        let today = chrono::Local::now().date_naive();
+        let goal = args.goal.and_then(|goal| match state_path("today") {
+            Some(path) => Some((goal, path)),
+            None => {
+                eprintln!("warning: no $HOME to keep --goal progress in.");
+                None
+            }
+        });
+        if let Some((goal, path)) = &goal {
+            println!("{}", goal::progress(goal::reviews_on(path, today), *goal));
+        }
+        let new_budgets = args
+            .rollover_new
+            .and_then(|max| match state_path("new_budget") {
+                Some(path) => {
+                    let budgets = read_new_budgets(&mut suite, &mut opts, &path, today, max);
+                    Some((budgets, path))
+                }
+                None => {
+                    eprintln!("warning: no $HOME to keep --rollover-new budget in.");
+                    None
+                }
+            });
+        let new_before = suite
+            .decks
+            .iter()
+            .map(|deck| deck.get_new())
+            .collect::<Vec<_>>();
+        if suite
+            .play(&opts, &mut input, &mut std::io::stdout())
+            .is_err()
+        {
+            eprintln!("error: stdin is not a terminal.");
+            eprintln!("pass --non-interactive to read answers from it anyway.");
+            exit(1);
+        }
+        let (budgets, budget_path) = new_budgets.unwrap_or_default();
+        for (decks, key, mut budget) in budgets {
+            budget.used += decks
+                .iter()
+                .map(|&i| {
+                    let deck = &suite.decks[i];
+                    new_before[i]
+                        .iter()
+                        .filter(|id| deck.played.contains(id))
+                        .count()
+                })
+                .sum::<usize>();
+            if let Err(err) = budget.save(&budget_path, &key, today) {
+                eprintln!("warning: could not save --rollover-new budget: {}", err);
+            }
+        }
+        if let Some((goal, path)) = &goal {
+            let played = suite.decks.iter().map(|deck| deck.played.len()).sum();
+            match goal::add_reviews(path, today, played) {
+                Ok(done) => println!("{}", goal::progress(done, *goal)),
+                Err(err) => eprintln!("warning: could not save --goal progress: {}", err),
+            }
+        }
+    }
+}
+
+// a --rollover-new budget for every deck with its own cap on new cards, and one
+// for the decks sharing --max-new, each with the indices of its decks and its key
+// in the state file. the caps are lowered to what is left of the budgets.
+fn read_new_budgets(
+    suite: &mut Suite,
+    opts: &mut PlayOptions,
+    path: &std::path::Path,
+    today: chrono::NaiveDate,
+    max_rollover: usize,
+) -> Vec<(Vec<usize>, String, NewBudget)> {
+    let mut budgets = vec![];
+    let mut shared = vec![];
+    for (i, deck) in suite.decks.iter_mut().enumerate() {
+        match suite::new_cap(deck, opts) {
+            Some(cap) => {
+                let key = deck::flat_name(&deck.path);
+                let budget = NewBudget::read(path, &key, today, cap, max_rollover);
+                deck.config.opts.new_cards = Some(budget.remaining());
+                budgets.push((vec![i], key, budget));
+            }
+            None => shared.push(i),
+        }
+    }
+    // every deck's percentage is now a fixed cap.
+    opts.new_percent = None;
+    if !shared.is_empty() {
+        let key = shared
+            .iter()
+            .map(|&i| deck::flat_name(&suite.decks[i].path))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let budget = NewBudget::read(path, &key, today, opts.max_new, max_rollover);
+        opts.max_new = budget.remaining();
+        budgets.push((shared, key, budget));
+    }
+    budgets
+}
+
+// small files kept between sessions, e.g. reviews done today for --goal.
+fn state_path(name: &str) -> Option<PathBuf> {
+    std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+        .map(|dir| dir.join("mnemo").join(name))
+}
+
+// previews the logs about to be rewritten and asks for confirmation. returns
+// whether to go ahead.
+fn confirm_rewrite(decks: &[&Deck], dry_run: bool, yes: bool) -> bool {
+    let files = decks
+        .iter()
+        .map(|deck| (deck.log_path.clone(), deck.log_contents()))
+        .collect::<Vec<_>>();
+    confirm_files(
+        &files,
+        dry_run,
+        yes,
+        &mut std::io::stdin().lock(),
+        &mut std::io::stdout(),
+    )
+}
+
+// the same for any files, given with their new contents.
+fn confirm_files(
+    files: &[(PathBuf, String)],
+    dry_run: bool,
+    yes: bool,
+    input: &mut dyn BufRead,
+    out: &mut dyn Write,
+) -> bool {
+    if yes && !dry_run {
+        return true;
+    }
+    let mut changed = false;
+    for (path, new) in files {
+        changed |= diff::preview_file(path, new, out).unwrap();
+    }
+    if !changed {
+        writeln!(out, "nothing to change.").unwrap();
+        return false;
+    }
+    !dry_run && diff::confirm(input, out)
+}
+
+// writes to --output if given, stdout otherwise. a closed stdout pipe is not an error.
+fn write_output<F>(output: &Option<PathBuf>, what: &str, f: F)
+where
+    F: FnOnce(&mut dyn Write) -> std::io::Result<()>,
+{
+    let mut out: Box<dyn Write> = match output {
+        Some(path) => match File::create(path) {
+            Ok(f) => Box::new(BufWriter::new(f)),
+            Err(err) => {
+                eprintln!("could not open {}: {}", path.to_string_lossy(), err);
+                exit(1);
+            }
+        },
+        None => Box::new(BufWriter::new(std::io::stdout().lock())),
+    };
+    match f(&mut out).and_then(|_| out.flush()) {
+        Ok(()) => {}
+        Err(err) if err.kind() == ErrorKind::BrokenPipe => {}
+        Err(err) => {
+            eprintln!("could not write output: {}", err);
+            exit(1);
+        }
+    }
+    if let Some(path) = output {
+        eprintln!("wrote {} to {}.", what, path.to_string_lossy());
+    }
+}
+
+// the ui itself only uses ascii, so plain output is uncolored output.
+fn apply_ascii(args: &Args) {
+    if args.ascii {
+        colored::control::set_override(false);
     }
 }
 
 fn parse() -> Args {
+    parse_from(std::env::args().collect()).unwrap_or_else(|code| exit(code))
+}
+
+// help and errors go to stdout and stderr. Err is the code to exit with.
+fn parse_from(argv: Vec<String>) -> Result<Args, i32> {
     let mut args = Args {
         files: vec![],
         randomize: false,
-        max_new: 10,
+        jitter_order: None,
+        priority: "oldest".to_string(),
+        minutes_per_deck: None,
+        ahead: 0,
+        min_session: None,
+        goal: None,
+        rollover_new: None,
+        playlist: None,
+        typed: false,
+        scaffold: false,
+        ease_step: None,
+        accuracy_thresholds: None,
+        log_session: None,
+        audio_command: None,
+        audio_rate: None,
+        audio_voice: None,
+        add_interactive: false,
+        write_next_due: None,
+        answers_file: None,
+        ascii: false,
+        new_percent: None,
+        warmup: 0,
+        new_every: None,
+        review_done: 0,
+        confidence: false,
+        unsure: false,
+        autosave_every: None,
+        speak_lang: None,
+        new_only: false,
+        auto_advance: None,
+        show_empty_cues: false,
+        non_interactive: false,
+        strip_parens: false,
+        scheduler: None,
+        keys: None,
+        max_new: None,
         max_old: None,
         add_cards: None,
+        import_format: "mnemo".to_string(),
         inspect: false,
+        backup: false,
+        validate: false,
+        swap_thresholds: "4,4".to_string(),
+        list_done: false,
+        count: None,
+        sort_by: None,
+        format: "default".to_string(),
         dump: false,
+        first_n: None,
+        last_n: None,
+        ids: None,
+        export_log: false,
+        study_sheet: false,
+        strip: false,
+        renumber: false,
+        trim_empty_cues: false,
+        hide_answers: false,
+        import_log: None,
+        diff: None,
+        seed_known: false,
+        replay: None,
+        normalize_factors: None,
+        pad_fields: false,
+        recover: false,
+        reformat: false,
+        align: false,
+        recursive: false,
+        due_now: None,
+        fix_timestamps: false,
+        seed_tag: None,
+        seed_factor: 1.0,
+        dry_run: false,
+        yes: false,
+        tags: false,
+        stats: false,
+        csv: false,
+        peek: None,
+        explain: None,
         conceal_number: false,
+        conceal_show_progress: false,
+        interval_mod: None,
+        tune: false,
+        target_retention: 90.0,
+        quiet: false,
+        relearn_gap: None,
+        preview: false,
+        pick: false,
+        doctor: false,
+        stdin: false,
+        output: None,
+        date_format: deck::DEFAULT_DATE_FORMAT.to_string(),
+        log_dir: None,
     };
 
     {
@@ -101,10 +1367,95 @@ fn parse() -> Args {
             argparse::StoreTrue,
             "randomize new cards",
         );
+        ap.refer(&mut args.jitter_order).add_option(
+            &["--jitter-order"],
+            argparse::StoreOption,
+            "lightly shuffle cards within windows of this size.",
+        );
+        ap.refer(&mut args.priority).add_option(
+            &["--priority"],
+            argparse::Store,
+            "order of due cards: oldest, or overdue to favor the most overdue with some variety.",
+        );
+        ap.refer(&mut args.minutes_per_deck).add_option(
+            &["--limit-session-by-minutes-per-deck"],
+            argparse::StoreOption,
+            "end the session after this many minutes, split fairly across decks.",
+        );
+        ap.refer(&mut args.ahead).add_option(
+            &["--ahead", "--review-ahead"],
+            argparse::Store,
+            "also review cards due within this many days.",
+        );
+        ap.refer(&mut args.goal).add_option(
+            &["--goal"],
+            argparse::StoreOption,
+            "show progress towards this many reviews a day.",
+        );
+        ap.refer(&mut args.rollover_new).add_option(
+            &["--rollover-new"],
+            argparse::StoreOption,
+            "make the new card caps daily allowances whose unused part carries over, up to this many cards.",
+        );
+        ap.refer(&mut args.min_session).add_option(
+            &["--min-session"],
+            argparse::StoreOption,
+            "review cards ahead of time until the session has this many cards.",
+        );
+        ap.refer(&mut args.playlist).add_option(
+            &["--playlist"],
+            argparse::StoreOption,
+            "play the cards listed as deck:id in this file, in order.",
+        );
+        ap.refer(&mut args.typed).add_option(
+            &["--typed"],
+            argparse::StoreTrue,
+            "type the answers, which are graded automatically.",
+        );
+        ap.refer(&mut args.scaffold).add_option(
+            &["--scaffold"],
+            argparse::StoreTrue,
+            "show the answer's shape as underscores, one per character.",
+        );
+        ap.refer(&mut args.non_interactive).add_option(
+            &["--non-interactive"],
+            argparse::StoreTrue,
+            "play even if stdin is not a terminal, e.g. with scripted answers.",
+        );
+        ap.refer(&mut args.add_interactive).add_option(
+            &["--add-interactive"],
+            argparse::StoreTrue,
+            "with --add-cards, ask for the fields missing from a card.",
+        );
+        ap.refer(&mut args.write_next_due).add_option(
+            &["--write-next-due"],
+            argparse::StoreOption,
+            "write the unix time the next card comes due to this file (0 if one is due).",
+        );
+        ap.refer(&mut args.answers_file).add_option(
+            &["--answers-file"],
+            argparse::StoreOption,
+            "grade the answers in this file, one per line, against the queued cards.",
+        );
+        ap.refer(&mut args.scheduler).add_option(
+            &["--scheduler"],
+            argparse::StoreOption,
+            "how intervals change: doubling (default) or leitner, back to one day when wrong.",
+        );
+        ap.refer(&mut args.strip_parens).add_option(
+            &["--answer-trim-parenthesized"],
+            argparse::StoreTrue,
+            "ignore trailing (parenthesized) context in typed answers.",
+        );
+        ap.refer(&mut args.keys).add_option(
+            &["--keys"],
+            argparse::StoreOption,
+            "grading keys, e.g. pass=space,fail=f. default pass=y,fail=n.",
+        );
         ap.refer(&mut args.max_new).add_option(
             &["-n", "--new-cards"],
-            argparse::Store,
-            "maximum # of new cards to show.",
+            argparse::StoreOption,
+            "maximum # of new cards to show. default 10.",
         );
         ap.refer(&mut args.max_old).add_option(
             &["-m", "--max-old-cards"],
@@ -116,26 +1467,429 @@ fn parse() -> Args {
             argparse::StoreTrue,
             "inspect .mnemo decks.",
         );
+        ap.refer(&mut args.backup).add_option(
+            &["--backup"],
+            argparse::StoreTrue,
+            "back up the decks and their logs now.",
+        );
+        ap.refer(&mut args.recover).add_option(
+            &["--recover"],
+            argparse::StoreTrue,
+            "restore a lost or broken deck from one of its backups. the log is kept.",
+        );
+        ap.refer(&mut args.validate).add_option(
+            &["--validate"],
+            argparse::StoreTrue,
+            "list cards that may have their answer and a cue swapped.",
+        );
+        ap.refer(&mut args.swap_thresholds).add_option(
+            &["--swap-thresholds"],
+            argparse::Store,
+            "for --validate, how many times longer than the answer a cue of how many words must be (default 4,4).",
+        );
+        ap.refer(&mut args.list_done).add_option(
+            &["--list-done"],
+            argparse::StoreTrue,
+            "list the cards that are done, by when they got there.",
+        );
+        ap.refer(&mut args.count).add_option(
+            &["--count"],
+            argparse::StoreOption,
+            "print how many cards are due, new, done or in total, and exit.",
+        );
+        ap.refer(&mut args.sort_by).add_option(
+            &["--sort-by"],
+            argparse::StoreOption,
+            "with --inspect, a table of decks sorted by due, new or total.",
+        );
+        ap.refer(&mut args.format).add_option(
+            &["--format"],
+            argparse::Store,
+            "with --inspect: default, or minimal for a `12d/5n` line that exits 1 if nothing is due.",
+        );
         ap.refer(&mut args.dump).add_option(
             &["-d", "--dump"],
             argparse::StoreTrue,
             "dump .mnemo decks.",
         );
+        ap.refer(&mut args.first_n).add_option(
+            &["--first-n"],
+            argparse::StoreOption,
+            "only dump/inspect the k lowest card ids.",
+        );
+        ap.refer(&mut args.last_n).add_option(
+            &["--last-n"],
+            argparse::StoreOption,
+            "only dump/inspect the k highest card ids.",
+        );
+        ap.refer(&mut args.ids).add_option(
+            &["--ids"],
+            argparse::StoreOption,
+            "only dump/inspect/strip/study-sheet these ids, e.g. 10-20,25,>100.",
+        );
+        ap.refer(&mut args.export_log).add_option(
+            &["--export-log"],
+            argparse::StoreTrue,
+            "print logs sorted by id, for syncing.",
+        );
+        ap.refer(&mut args.study_sheet).add_option(
+            &["--study-sheet"],
+            argparse::StoreTrue,
+            "print the cards as an html table for printing.",
+        );
+        ap.refer(&mut args.strip).add_option(
+            &["--strip"],
+            argparse::StoreTrue,
+            "print the deck with only ids, answers and cues, for sharing.",
+        );
+        ap.refer(&mut args.renumber).add_option(
+            &["--renumber"],
+            argparse::StoreTrue,
+            "number the cards from 1 in file order, moving their logs along. with \
+             --strip, only in the export.",
+        );
+        ap.refer(&mut args.trim_empty_cues).add_option(
+            &["--trim-empty-cues"],
+            argparse::StoreTrue,
+            "with --strip, leave out trailing cue columns that are empty for every card.",
+        );
+        ap.refer(&mut args.hide_answers).add_option(
+            &["--hide-answers"],
+            argparse::StoreTrue,
+            "with --study-sheet, put the answers on a separate page.",
+        );
+        ap.refer(&mut args.import_log).add_option(
+            &["--import-log"],
+            argparse::StoreOption,
+            "merge a log into the deck's log, keeping the latest entry per card.",
+        );
+        ap.refer(&mut args.diff).add_option(
+            &["--diff"],
+            argparse::StoreOption,
+            "list cards added, removed or changed in another version of the deck.",
+        );
+        ap.refer(&mut args.fix_timestamps).add_option(
+            &["--fix-timestamps"],
+            argparse::StoreTrue,
+            "reschedule cards whose due date is implausibly far ahead or before they were added.",
+        );
+        ap.refer(&mut args.pad_fields).add_option(
+            &["--pad-fields"],
+            argparse::StoreTrue,
+            "add empty cues to cards with fewer fields than the rest of the deck.",
+        );
+        ap.refer(&mut args.reformat).add_option(
+            &["--reformat"],
+            argparse::StoreTrue,
+            "rewrite the cards as `id | answer | cue`, changing nothing else.",
+        );
+        ap.refer(&mut args.align).add_option(
+            &["--align"],
+            argparse::StoreTrue,
+            "with --reformat, line up the columns.",
+        );
+        ap.refer(&mut args.recursive).add_option(
+            &["--recursive"],
+            argparse::StoreTrue,
+            "also look for decks in subdirectories of directories given.",
+        );
+        ap.refer(&mut args.due_now).add_option(
+            &["--due-now", "--touch"],
+            argparse::StoreOption,
+            "make the card with this id due now.",
+        );
+        ap.refer(&mut args.replay).add_option(
+            &["--replay"],
+            argparse::StoreOption,
+            "replay the last session in this .history file onto the log as it was before, and print the resulting log. saves nothing.",
+        );
+        ap.refer(&mut args.normalize_factors).add_option(
+            &["--normalize-factors"],
+            argparse::StoreOption,
+            "bring the factors in the log between 1 and this cap, e.g. for a shared deck.",
+        );
+        ap.refer(&mut args.seed_known).add_option(
+            &["--seed-known"],
+            argparse::StoreTrue,
+            "mark all new cards as learned.",
+        );
+        ap.refer(&mut args.seed_tag).add_option(
+            &["--seed-tag"],
+            argparse::StoreOption,
+            "only seed new cards with this tag.",
+        );
+        ap.refer(&mut args.seed_factor).add_option(
+            &["--seed-factor"],
+            argparse::Store,
+            "starting factor, and days until due, of seeded cards. default 1.",
+        );
+        ap.refer(&mut args.dry_run).add_option(
+            &["--dry-run"],
+            argparse::StoreTrue,
+            "show what would change in rewritten files without writing them.",
+        );
+        ap.refer(&mut args.yes).add_option(
+            &["-y", "--yes"],
+            argparse::StoreTrue,
+            "don't ask before rewriting files.",
+        );
+        ap.refer(&mut args.peek).add_option(
+            &["-p", "--peek"],
+            argparse::StoreOption,
+            "show a card and its scheduling.",
+        );
+        ap.refer(&mut args.explain).add_option(
+            &["--explain"],
+            argparse::StoreOption,
+            "show why a card would or would not come up in a session with the other options.",
+        );
+        ap.refer(&mut args.tags).add_option(
+            &["--tags"],
+            argparse::StoreTrue,
+            "list tags with their card counts.",
+        );
+        ap.refer(&mut args.stats).add_option(
+            &["--stats"],
+            argparse::StoreTrue,
+            "show card counts and the number of reviews due each day this week.",
+        );
+        ap.refer(&mut args.csv).add_option(
+            &["--csv"],
+            argparse::StoreTrue,
+            "with --stats, print rows of date,deck,new,due,done,total,day,count. dates are always iso.",
+        );
+        ap.refer(&mut args.output).add_option(
+            &["-o", "--output"],
+            argparse::StoreOption,
+            "write dump output to a file instead of stdout.",
+        );
+        ap.refer(&mut args.date_format).add_option(
+            &["--date-format"],
+            argparse::Store,
+            "strftime format for printed dates. default %Y-%m-%d.",
+        );
+        ap.refer(&mut args.log_dir).add_option(
+            &["--log-dir"],
+            argparse::StoreOption,
+            "keep logs in this directory instead of next to the decks. default $MNEMO_LOG_DIR.",
+        );
+        ap.refer(&mut args.review_done).add_option(
+            &["--review-done"],
+            argparse::Store,
+            "also review this many random done cards. forgotten ones go back into rotation.",
+        );
+        ap.refer(&mut args.confidence).add_option(
+            &["--confidence"],
+            argparse::StoreTrue,
+            "after grading, also ask whether you were sure. kept in <deck>.history.",
+        );
+        ap.refer(&mut args.unsure).add_option(
+            &["--unsure"],
+            argparse::StoreTrue,
+            "list cards answered correctly without being sure.",
+        );
+        ap.refer(&mut args.autosave_every).add_option(
+            &["--autosave-every"],
+            argparse::StoreOption,
+            "save the logs after every n answers.",
+        );
+        ap.refer(&mut args.speak_lang).add_option(
+            &["--speak-lang"],
+            argparse::StoreOption,
+            "speak the cues in this language with trans (see # lang:).",
+        );
+        ap.refer(&mut args.ease_step).add_option(
+            &["--ease-step"],
+            argparse::StoreOption,
+            "how much + and - at the grade prompt change a card's factor (default 0.2, i.e. 20%).",
+        );
+        ap.refer(&mut args.accuracy_thresholds).add_option(
+            &["--accuracy-thresholds"],
+            argparse::StoreOption,
+            "accuracy in percent below which the summary is red and above which it is green (default 80,95).",
+        );
+        ap.refer(&mut args.log_session).add_option(
+            &["--log-session"],
+            argparse::StoreOption,
+            "append a readable line per answer to this file.",
+        );
+        ap.refer(&mut args.audio_command).add_option(
+            &["--audio-command"],
+            argparse::StoreOption,
+            "command to speak with instead of trans, using {text}, {lang}, {rate} and {voice}.",
+        );
+        ap.refer(&mut args.audio_rate).add_option(
+            &["--audio-rate"],
+            argparse::StoreOption,
+            "{rate} for --audio-command.",
+        );
+        ap.refer(&mut args.audio_voice).add_option(
+            &["--audio-voice"],
+            argparse::StoreOption,
+            "{voice} for --audio-command.",
+        );
+        ap.refer(&mut args.new_percent).add_option(
+            &["--new-percent"],
+            argparse::StoreOption,
+            "cap new cards of each deck at this percentage of its cards, instead of -n.",
+        );
+        ap.refer(&mut args.warmup).add_option(
+            &["--warmup"],
+            argparse::Store,
+            "start with up to n of the most mature due cards.",
+        );
+        ap.refer(&mut args.new_every).add_option(
+            &["--new-every"],
+            argparse::StoreOption,
+            "introduce a new card after every k due cards.",
+        );
+        ap.refer(&mut args.new_only).add_option(
+            &["--new-only"],
+            argparse::StoreTrue,
+            "only introduce new cards, skipping due ones.",
+        );
+        ap.refer(&mut args.auto_advance).add_option(
+            &["--auto-advance"],
+            argparse::StoreOption,
+            "reveal answers after this many seconds, without waiting for enter.",
+        );
+        ap.refer(&mut args.show_empty_cues).add_option(
+            &["--show-empty-cues"],
+            argparse::StoreTrue,
+            "show every cue's header, even when the cue is empty.",
+        );
+        ap.refer(&mut args.ascii).add_option(
+            &["--ascii"],
+            argparse::StoreTrue,
+            "plain ascii output without colors, for limited terminals.",
+        );
+        ap.refer(&mut args.conceal_show_progress).add_option(
+            &["--conceal-show-progress"],
+            argparse::StoreTrue,
+            "conceal card number, showing how far into the session a card is instead.",
+        );
         ap.refer(&mut args.conceal_number).add_option(
             &["-c", "--conceal-number"],
             argparse::StoreTrue,
             "conceal card number",
         );
+        ap.refer(&mut args.interval_mod).add_option(
+            &["--interval-mod"],
+            argparse::StoreOption,
+            "multiply new intervals by this factor.",
+        );
+        ap.refer(&mut args.tune).add_option(
+            &["--tune"],
+            argparse::StoreTrue,
+            "suggest an --interval-mod from the logged retention.",
+        );
+        ap.refer(&mut args.target_retention).add_option(
+            &["--target-retention"],
+            argparse::Store,
+            "retention (in %) that --tune aims for. default 90.",
+        );
+        ap.refer(&mut args.quiet).add_option(
+            &["-q", "--quiet"],
+            argparse::StoreTrue,
+            "don't print deck path and scheduling info while playing.",
+        );
+        ap.refer(&mut args.relearn_gap).add_option(
+            &["--relearn-gap"],
+            argparse::StoreOption,
+            "show a repeated card again after this many other cards.",
+        );
+        ap.refer(&mut args.preview).add_option(
+            &["--preview"],
+            argparse::StoreTrue,
+            "read through cards with answers shown, without grading.",
+        );
+        ap.refer(&mut args.pick).add_option(
+            &["--pick"],
+            argparse::StoreTrue,
+            "choose which decks to study before starting.",
+        );
         ap.refer(&mut args.add_cards).add_option(
             &["-a", "--add-cards"],
             argparse::StoreOption,
             "append new cards to a .mnemo file.",
         );
+        ap.refer(&mut args.import_format).add_option(
+            &["--import-format"],
+            argparse::Store,
+            "format of the cards given to -a: mnemo (default) or mnemosyne.",
+        );
+        ap.refer(&mut args.doctor).add_option(
+            &["--doctor"],
+            argparse::StoreTrue,
+            "check environment and decks for problems.",
+        );
+        ap.refer(&mut args.stdin).add_option(
+            &["--stdin"],
+            argparse::StoreTrue,
+            "read a single deck from stdin. scheduling is not saved.",
+        );
         ap.refer(&mut args.files)
             .add_argument("file", argparse::Collect, ".mnemo decks to play");
 
-        ap.parse_args_or_exit();
+        ap.parse(argv, &mut std::io::stdout(), &mut std::io::stderr())?;
     }
 
-    args
+    Ok(args)
+}
+
+#[cfg(test)]
+mod test_main {
+    use super::*;
+    use colored::Colorize;
+
+    #[test]
+    fn test_ascii() {
+        let argv = ["mnemo", "--ascii", "deck.mnemo"]
+            .map(String::from)
+            .to_vec();
+        let args = parse_from(argv).unwrap();
+        assert!(args.ascii);
+        apply_ascii(&args);
+        assert_eq!("failed.".red().to_string(), "failed.");
+    }
+
+    #[test]
+    fn test_renumber_dry_run() {
+        const DECK: &str = "tests/test_renumber_copy.mnemo";
+        const LOG: &str = "tests/test_renumber_copy.mnemo.log";
+        std::fs::copy("tests/test_renumber.mnemo", DECK).unwrap();
+        std::fs::copy("tests/test_renumber.mnemo.log", LOG).unwrap();
+
+        let mut d = Deck::read_from_file(std::path::Path::new(DECK), None).unwrap();
+        let files = d.renumber().unwrap();
+        let mut out = vec![];
+        assert!(!confirm_files(
+            &files,
+            true,
+            false,
+            &mut "y\n".as_bytes(),
+            &mut out
+        ));
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "--- {DECK}\n+++ {DECK}\n\
+                 -3 | a      | x\n-7 | b      | y\n-12 | c     | z\n\
+                 +1 | a      | x\n+2 | b      | y\n+3 | c     | z\n\
+                 --- {LOG}\n+++ {LOG}\n\
+                 -3,100000000,2.00\n-12,200000000,4.00\n+1,100000000,2.00\n+3,200000000,4.00\n"
+            )
+        );
+        assert_eq!(
+            std::fs::read_to_string(DECK).unwrap(),
+            std::fs::read_to_string("tests/test_renumber.mnemo").unwrap()
+        );
+        assert_eq!(
+            std::fs::read_to_string(LOG).unwrap(),
+            std::fs::read_to_string("tests/test_renumber.mnemo.log").unwrap()
+        );
+
+        std::fs::remove_file(DECK).unwrap();
+        std::fs::remove_file(LOG).unwrap();
+    }
 }
@@ -0,0 +1,153 @@
+use std::{str::FromStr, sync::Arc};
+
+use crate::card::Status;
+
+// how well a card was answered.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Grade {
+    Wrong,
+    Right,
+}
+
+impl From<bool> for Grade {
+    fn from(correct: bool) -> Grade {
+        if correct {
+            Grade::Right
+        } else {
+            Grade::Wrong
+        }
+    }
+}
+
+// how an answer changes the factor, the interval in days, of a card finishing its
+// ticks. Status::update takes care of everything else, e.g. the learning steps
+// and the due date.
+pub trait Scheduler {
+    fn update(&self, status: &mut Status, grade: Grade);
+}
+
+// so that a Config holding any scheduler can still be printed.
+impl std::fmt::Debug for dyn Scheduler {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Scheduler")
+    }
+}
+
+// doubles the interval on a right answer and halves it on a wrong one.
+pub struct Doubling;
+
+// like moving a card between Leitner boxes: a right answer doubles the interval,
+// a wrong one sends the card back to the first box.
+pub struct Leitner;
+
+impl Scheduler for Doubling {
+    fn update(&self, status: &mut Status, grade: Grade) {
+        match grade {
+            Grade::Right => status.factor *= 2.0,
+            Grade::Wrong => status.factor /= 2.0,
+        }
+        status.factor = status.factor.max(1.0);
+    }
+}
+
+impl Scheduler for Leitner {
+    fn update(&self, status: &mut Status, grade: Grade) {
+        status.factor = match grade {
+            Grade::Right => (status.factor * 2.0).max(1.0),
+            Grade::Wrong => 1.0,
+        };
+    }
+}
+
+// the built-in schedulers, by the names --scheduler takes.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SchedulerKind {
+    Doubling,
+    Leitner,
+}
+
+impl SchedulerKind {
+    pub fn scheduler(self) -> Arc<dyn Scheduler> {
+        match self {
+            SchedulerKind::Doubling => Arc::new(Doubling),
+            SchedulerKind::Leitner => Arc::new(Leitner),
+        }
+    }
+}
+
+impl FromStr for SchedulerKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "doubling" => Ok(SchedulerKind::Doubling),
+            "leitner" => Ok(SchedulerKind::Leitner),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_scheduler {
+    use super::*;
+
+    fn factors(scheduler: &dyn Scheduler, answers: &[Grade]) -> Vec<f64> {
+        let mut status = Status {
+            factor: 4.0,
+            ..Status::new(1)
+        };
+        answers
+            .iter()
+            .map(|&grade| {
+                scheduler.update(&mut status, grade);
+                status.factor
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_schedulers() {
+        let answers = [true, true, false, true, false, false].map(Grade::from);
+        assert_eq!(
+            factors(&Doubling, &answers),
+            vec![8.0, 16.0, 8.0, 16.0, 8.0, 4.0]
+        );
+        assert_eq!(
+            factors(&Leitner, &answers),
+            vec![8.0, 16.0, 1.0, 2.0, 1.0, 1.0]
+        );
+        assert_eq!(
+            factors(SchedulerKind::Leitner.scheduler().as_ref(), &answers),
+            factors(&Leitner, &answers)
+        );
+
+        // graduating from the learning steps starts at one day either way.
+        for scheduler in [&Doubling as &dyn Scheduler, &Leitner] {
+            let mut status = Status::new(1);
+            scheduler.update(&mut status, Grade::Right);
+            assert_eq!(status.factor, 1.0);
+        }
+
+        // any scheduler can be plugged into a deck's config.
+        struct Weekly;
+        impl Scheduler for Weekly {
+            fn update(&self, status: &mut Status, _: Grade) {
+                status.factor = 7.0;
+            }
+        }
+        let config = crate::config::Config {
+            scheduler: Arc::new(Weekly),
+            ..Default::default()
+        };
+        let mut status = Status {
+            factor: 4.0,
+            ticks: 1,
+            ..Status::new(1)
+        };
+        status.update(false, false, &config, chrono::Local::now());
+        assert_eq!(status.factor, 7.0);
+
+        assert_eq!("leitner".parse(), Ok(SchedulerKind::Leitner));
+        assert!("sm2".parse::<SchedulerKind>().is_err());
+    }
+}
@@ -1,124 +1,425 @@
 use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    io::{BufRead, IsTerminal, Write},
     path::{Path, PathBuf},
-    process::exit,
+    str::FromStr,
+    time::{Duration, Instant},
 };
 
-use rand::{seq::SliceRandom, thread_rng};
+use rand::{seq::SliceRandom, Rng, RngExt};
 
-use crate::deck::{Deck, DeckErr};
-use colored::Colorize;
+use crate::{
+    answer,
+    audio::AudioCommand,
+    card::{Card, Status},
+    deck::{self, Deck, DeckErr},
+    keymap::KeyMap,
+};
+use colored::{Color, Colorize};
 
 pub struct Suite {
     pub decks: Vec<Deck>,
 }
 
+#[derive(Debug, Default)]
+pub struct PlayOptions {
+    pub max_new: usize,
+    pub max_old: Option<usize>,
+    pub randomize: bool,
+    pub conceal_number: bool,
+    // with conceal_number, show how far into the session a card is instead.
+    pub conceal_progress: bool,
+    pub quiet: bool,
+    // minimum number of other cards between repeats of a card.
+    pub relearn_gap: Option<usize>,
+    // show cards with their answers, without grading or scheduling.
+    pub preview: bool,
+    // shuffle only within windows of this many cards, keeping the rough order.
+    pub jitter_order: Option<usize>,
+    // session length in minutes, split across decks in proportion to their
+    // queued cards. a deck is skipped once its share is used up.
+    pub minutes_per_deck: Option<f64>,
+    // also review learned cards due within this many days.
+    pub ahead: u32,
+    pub keys: KeyMap,
+    // pad sessions shorter than this with the cards due soonest.
+    pub min_session: Option<usize>,
+    // (deck index, id) pairs played in exactly this order, due or not.
+    pub playlist: Option<Vec<(usize, usize)>>,
+    // type the answer instead of revealing it; graded by comparison.
+    pub typed: bool,
+    // shows the answer's shape as underscores before the reveal.
+    pub scaffold: bool,
+    // caps new cards per deck at this percentage of its size, instead of max_new.
+    pub new_percent: Option<f64>,
+    // start with up to this many of the most mature due cards.
+    pub warmup: usize,
+    // introduce a new card after every this many due ones, instead of after all of them.
+    pub new_every: Option<usize>,
+    // also play this many random done cards.
+    pub review_done: usize,
+    // `+` and `-` at the grade prompt scale the factor by this much, EASE_STEP if not given.
+    pub ease_step: Option<f64>,
+    // also ask how sure the answer was, and keep both in the deck's history.
+    pub confidence: bool,
+    // keep every grade in the deck's history, which --replay reads.
+    pub record_history: bool,
+    // append a readable line per answer to this file.
+    pub transcript: Option<PathBuf>,
+    // save the logs after this many answers, even if no card was finished.
+    pub autosave_every: Option<usize>,
+    // speak the cues in this language with `audio`.
+    pub speak_lang: Option<String>,
+    pub audio: AudioCommand,
+    // skip due cards and only introduce new ones.
+    pub new_only: bool,
+    // reveal after this many seconds instead of waiting for enter.
+    pub auto_advance: Option<f64>,
+    // print empty cues with their headers so every card has the same layout.
+    pub show_empty_cues: bool,
+    // answers are scripted, so don't insist on a terminal.
+    pub non_interactive: bool,
+    pub due_order: DueOrder,
+    pub accuracy_thresholds: AccuracyThresholds,
+}
+
+// the summary shows accuracies below `red` in red and above `green` in green,
+// yellow in between. `red,green` in percent, e.g. `80,95`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct AccuracyThresholds {
+    pub red: f64,
+    pub green: f64,
+}
+
+impl Default for AccuracyThresholds {
+    fn default() -> Self {
+        AccuracyThresholds {
+            red: 80.0,
+            green: 95.0,
+        }
+    }
+}
+
+impl AccuracyThresholds {
+    pub fn color(&self, percentage: f64) -> Color {
+        if percentage < self.red {
+            Color::Red
+        } else if percentage > self.green {
+            Color::Green
+        } else {
+            Color::Yellow
+        }
+    }
+}
+
+impl FromStr for AccuracyThresholds {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (red, green) = s.split_once(',').ok_or(())?;
+        let red = red.trim().parse::<f64>().map_err(|_| ())?;
+        let green = green.trim().parse::<f64>().map_err(|_| ())?;
+        if !(0.0 <= red && red <= green && green <= 100.0) {
+            return Err(());
+        }
+        Ok(AccuracyThresholds { red, green })
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub enum DueOrder {
+    // by due date.
+    #[default]
+    Oldest,
+    // random, but weighted by days overdue.
+    Overdue,
+}
+
+// columns --inspect can sort decks by, most first.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SortBy {
+    Due,
+    New,
+    Total,
+}
+
+// what --count counts.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Bucket {
+    Due,
+    New,
+    Done,
+    Total,
+}
+
+impl FromStr for Bucket {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "due" => Ok(Bucket::Due),
+            "new" => Ok(Bucket::New),
+            "done" => Ok(Bucket::Done),
+            "total" => Ok(Bucket::Total),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PlayErr {
+    NotInteractive,
+}
+
 impl Suite {
-    pub fn read_from_files(paths: &[PathBuf]) -> Result<Suite, (PathBuf, DeckErr)> {
+    pub fn read_from_files(
+        paths: &[PathBuf],
+        log_dir: Option<&Path>,
+    ) -> Result<Suite, (PathBuf, DeckErr)> {
         let decks = paths
             .iter()
-            .map(|path| Deck::read_from_file(path).map_err(|err| (path.clone(), err)))
+            .map(|path| Deck::read_from_file(path, log_dir).map_err(|err| (path.clone(), err)))
             .collect::<Result<_, _>>()?;
         Ok(Suite { decks })
     }
 
     pub fn play(
         &mut self,
-        max_new: usize,
-        max_old: Option<usize>,
-        randomize: bool,
-        conceal_number: bool,
-    ) {
-        for deck in self.decks.iter() {
-            deck.backup_log();
-        }
+        opts: &PlayOptions,
+        input: &mut dyn BufRead,
+        out: &mut dyn Write,
+    ) -> Result<(), PlayErr> {
+        check_interactive(opts, std::io::stdin().is_terminal())?;
+        let start = Instant::now();
+        self.play_with_clock(opts, input, out, &mut || start.elapsed());
+        Ok(())
+    }
 
-        let on_exit = |decks: &[Deck]| {
+    // the clock returns the time since any fixed point; only differences are used.
+    fn play_with_clock(
+        &mut self,
+        opts: &PlayOptions,
+        input: &mut dyn BufRead,
+        out: &mut dyn Write,
+        clock: &mut dyn FnMut() -> Duration,
+    ) {
+        let on_exit = |decks: &[Deck], out: &mut dyn Write| {
+            if opts.preview {
+                return;
+            }
             for deck in decks.iter() {
                 let played = deck.played.len();
                 let wrong = deck.wrong.len();
                 let right = played - wrong;
                 let percentage = right as f64 / played as f64 * 100.0;
-                println!(
+                writeln!(
+                    out,
                     "{}: {} ({}/{}).",
                     deck.path.to_string_lossy().green(),
-                    {
-                        let txt = format!("{:.1}%", percentage);
-                        if percentage < 80.0 {
-                            txt.red()
-                        } else if percentage > 95.0 {
-                            txt.green()
-                        } else {
-                            txt.yellow()
-                        }
-                    },
+                    format!("{:.1}%", percentage).color(opts.accuracy_thresholds.color(percentage)),
                     right,
                     played,
-                );
+                )
+                .unwrap();
                 if wrong > 0 {
-                    println!("got {} wrong:", wrong);
+                    writeln!(out, "got {} wrong:", wrong).unwrap();
                     for id in deck.wrong.iter() {
-                        println!("{}: {}", id, deck.cards[id].answer);
+                        writeln!(out, "{}: {}", id, deck.cards[id].answer).unwrap();
                     }
                 }
             }
         };
 
-        macro_rules! play {
-            ($deck: ident) => {
-                let mut done = false;
-                while !done {
-                    done = true;
-                    for &(deck_index, id) in $deck.iter() {
-                        let deck = &mut self.decks[deck_index];
-                        if deck
-                            .status
-                            .get(&id)
-                            .map(|status| status.is_due())
-                            .unwrap_or(true)
-                        {
-                            done = false;
-                            if !deck.play_card(id, conceal_number) {
-                                on_exit(&self.decks);
-                                exit(0);
-                            }
-                        }
-                    }
+        let mut queue = self.get_queue(opts);
+        if opts.playlist.is_none() {
+            for note in self.new_shortfalls(opts) {
+                writeln!(out, "{}", note).unwrap();
+            }
+        }
+        // padding for --min-session, reviewed ahead of time.
+        let mut early = HashSet::new();
+        if let Some(min) = opts.min_session.filter(|_| !opts.new_only) {
+            for item in self.get_upcoming(min.saturating_sub(queue.len()), opts) {
+                early.insert(item);
+                queue.push_back(item);
+            }
+        }
+
+        // done cards pulled back in for a refresher.
+        if !opts.new_only {
+            for item in self.sample_done(opts.review_done, &mut rand::rng()) {
+                early.insert(item);
+                queue.push_back(item);
+            }
+        }
+
+        if queue.is_empty() {
+            writeln!(out, "nothing to review, you're all caught up!").unwrap();
+            return;
+        }
+        if !opts.preview {
+            for deck in self.decks.iter() {
+                deck.backup_session_log();
+            }
+        }
+
+        let budgets = opts.minutes_per_deck.map(|minutes| {
+            let mut budgets = vec![Duration::ZERO; self.decks.len()];
+            for (deck_index, _) in queue.iter() {
+                budgets[*deck_index] +=
+                    Duration::from_secs_f64(minutes * 60.0 / queue.len() as f64);
+            }
+            budgets
+        });
+        let mut elapsed = vec![Duration::ZERO; self.decks.len()];
+        let mut graded = 0;
+        let mut shown = 0;
+
+        while let Some((deck_index, id)) = queue.pop_front() {
+            if let Some(budgets) = &budgets {
+                if elapsed[deck_index] >= budgets[deck_index] {
+                    continue;
                 }
+            }
+            let deck = &mut self.decks[deck_index];
+            let is_due = |deck: &Deck| {
+                deck.status
+                    .get(&id)
+                    .map(|status| status.is_due_within(opts.ahead))
+                    .unwrap_or(true)
             };
+            let forced = opts.playlist.is_some()
+                || early.remove(&(deck_index, id))
+                || deck.cards.get(&id).is_some_and(Card::is_pinned);
+            if !is_due(deck) && !forced {
+                continue;
+            }
+            if let Some(status) = deck.status.get_mut(&id) {
+                // a card that was already played to the end can be played again.
+                status.ticks = status.ticks.max(1);
+            }
+            shown += 1;
+            let position = (shown, shown + queue.len());
+            let before = clock();
+            let go_on = deck.play_card(id, Some(position), opts, input, out);
+            elapsed[deck_index] += clock().saturating_sub(before);
+            if !go_on {
+                on_exit(&self.decks, out);
+                return;
+            }
+            if !opts.preview && opts.playlist.is_none() && is_due(deck) {
+                requeue(&mut queue, (deck_index, id), opts.relearn_gap);
+            }
+            if !opts.preview {
+                graded += 1;
+                if opts.autosave_every.is_some_and(|n| graded % n == 0) {
+                    for deck in self.decks.iter() {
+                        deck.save_log();
+                    }
+                }
+            }
         }
 
-        let old = self.get_due(max_old, randomize);
-        play!(old);
+        on_exit(&self.decks, out);
+    }
 
-        let new = self.get_new(Some(max_new), randomize);
-        play!(new);
+    // the cards a session starts with, in order.
+    fn get_queue(&mut self, opts: &PlayOptions) -> VecDeque<(usize, usize)> {
+        let mut queue = match &opts.playlist {
+            Some(playlist) => playlist.iter().copied().collect(),
+            None if opts.new_only => self.get_new(opts).into(),
+            None => {
+                let (due, new) = (self.get_due(opts), self.get_new(opts));
+                match opts.new_every {
+                    Some(k) => interleave(due, new, k),
+                    None => due.into_iter().chain(new).collect::<VecDeque<_>>(),
+                }
+            }
+        };
+        if opts.playlist.is_none() && opts.warmup > 0 {
+            self.warm_up(&mut queue, opts.warmup);
+        }
+        if opts.playlist.is_none() {
+            let pinned = self.get_pinned();
+            queue.retain(|item| !pinned.contains(item));
+            for item in pinned.into_iter().rev() {
+                queue.push_front(item);
+            }
+        }
+        queue
+    }
 
-        on_exit(&self.decks);
+    // grades `answers`, one line per card of the session queue, as typed answers.
+    // nothing is scheduled. cards without an answer line count as wrong. returns
+    // the number of right answers and of cards.
+    pub fn grade_answers(
+        &mut self,
+        opts: &PlayOptions,
+        answers: &str,
+        out: &mut dyn Write,
+    ) -> (usize, usize) {
+        let queue = self.get_queue(opts);
+        let mut answers = answers.lines();
+        let mut right = 0;
+        for &(deck_index, id) in queue.iter() {
+            let deck = &self.decks[deck_index];
+            let expected = &deck.cards[&id].answer;
+            let typed = answers.next().unwrap_or("");
+            if answer::matches(typed, expected, deck.config.strip_parens) {
+                right += 1;
+                writeln!(out, "#{}: {} {}", id, expected, "ok.".green()).unwrap();
+            } else {
+                writeln!(
+                    out,
+                    "#{}: {} {} (answered `{}`)",
+                    id,
+                    expected,
+                    "failed.".red(),
+                    typed.trim()
+                )
+                .unwrap();
+            }
+        }
+        let extra = answers.count();
+        if extra > 0 {
+            writeln!(out, "unused answers: {}.", extra).unwrap();
+        }
+        writeln!(out, "score: {}/{}.", right, queue.len()).unwrap();
+        (right, queue.len())
     }
 
-    fn get_due_or_new<F>(
+    // `max` caps the cards over all decks, except for decks with their own cap
+    // from `deck_max`, which only count against that.
+    fn get_due_or_new<F, M>(
         &mut self,
         get_fn: F,
         max: Option<usize>,
-        randomize: bool,
+        deck_max: M,
+        opts: &PlayOptions,
     ) -> Vec<(usize, usize)>
     where
         F: Fn(&Deck) -> Vec<usize>,
+        M: Fn(&Deck) -> Option<usize>,
     {
         let mut decks = self.decks.iter().map(get_fn).collect::<Vec<_>>();
-
-        if randomize {
-            for deck in decks.iter_mut() {
-                deck.shuffle(&mut thread_rng());
+        let deck_maxes = self.decks.iter().map(deck_max).collect::<Vec<_>>();
+        // shuffled before capping, so that randomizing picks from the whole deck.
+        for (deck, cards) in self.decks.iter().zip(decks.iter_mut()) {
+            if opts.randomize || deck.config.opts.randomize {
+                cards.shuffle(&mut rand::rng());
+            } else if let Some(window) = opts.jitter_order {
+                jitter(cards, window, &mut rand::rng());
+            }
+        }
+        for (deck, deck_max) in decks.iter_mut().zip(deck_maxes.iter()) {
+            if let Some(deck_max) = deck_max {
+                deck.truncate(*deck_max);
             }
         }
 
         if self.decks.len() == 1 {
-            return decks[0]
-                .iter()
-                .take(max.unwrap_or(decks[0].len()))
-                .map(|&c| (0, c))
-                .collect();
+            let max = deck_maxes[0].or(max).unwrap_or(decks[0].len());
+            return decks[0].iter().take(max).map(|&c| (0, c)).collect();
         } else {
             for deck in decks.iter_mut() {
                 deck.reverse();
@@ -126,14 +427,18 @@ impl Suite {
         }
 
         let mut ret = vec![];
+        let mut counted = 0;
         while decks.iter().any(|deck| !deck.is_empty()) {
             for (deck_index, cards) in decks.iter_mut().enumerate() {
+                let own_max = deck_maxes[deck_index].is_some();
+                if !own_max && max.is_some_and(|max| counted >= max) {
+                    cards.clear();
+                    continue;
+                }
                 if let Some(card) = cards.pop() {
                     ret.push((deck_index, card));
-                }
-                if let Some(max) = max {
-                    if ret.len() >= max {
-                        break;
+                    if !own_max {
+                        counted += 1;
                     }
                 }
             }
@@ -141,19 +446,579 @@ impl Suite {
         ret
     }
 
-    pub fn get_due(&mut self, max: Option<usize>, randomize: bool) -> Vec<(usize, usize)> {
-        self.get_due_or_new(Deck::get_due, max, randomize)
+    // asks which decks to study and drops the others. an empty answer keeps all decks.
+    pub fn pick(&mut self, input: &mut dyn BufRead, out: &mut dyn Write) {
+        for (i, deck) in self.decks.iter().enumerate() {
+            writeln!(
+                out,
+                "{}) {}: {} due, {} new",
+                i + 1,
+                deck.path.to_string_lossy().green(),
+                deck.get_due().len(),
+                deck.get_new().len(),
+            )
+            .unwrap();
+        }
+
+        let selection = loop {
+            write!(out, "decks to study (e.g. 1 3, empty for all): ").unwrap();
+            out.flush().unwrap();
+            let mut ans = String::new();
+            if input.read_line(&mut ans).unwrap_or(0) == 0 {
+                return;
+            }
+            match parse_selection(&ans, self.decks.len()) {
+                Some(selection) => break selection,
+                None => writeln!(out, "invalid selection.").unwrap(),
+            }
+        };
+
+        if !selection.is_empty() {
+            let mut i = 0;
+            self.decks.retain(|_| {
+                i += 1;
+                selection.contains(&(i - 1))
+            });
+        }
+    }
+
+    // fraction of reviews that were correct, with the number of reviews. graduations
+    // count as correct reviews, wrong answers while learning don't count at all.
+    pub fn retention(&self) -> Option<(f64, usize)> {
+        let (reviews, lapses) = self
+            .decks
+            .iter()
+            .flat_map(|deck| deck.status.values())
+            .fold((0, 0), |(reviews, lapses), status| {
+                (reviews + status.reviews, lapses + status.lapses)
+            });
+        let total = reviews + lapses;
+        (total > 0).then(|| (reviews as f64 / total as f64, total))
+    }
+
+    // due and new cards over all decks.
+    pub fn due_and_new(&self) -> (usize, usize) {
+        self.decks.iter().fold((0, 0), |(due, new), deck| {
+            (due + deck.get_due().len(), new + deck.get_new().len())
+        })
+    }
+
+    // cards in `bucket` over all decks.
+    pub fn count(&self, bucket: Bucket) -> usize {
+        self.decks
+            .iter()
+            .map(|deck| match bucket {
+                Bucket::Due => deck.get_due().len(),
+                Bucket::New => deck.get_new().len(),
+                Bucket::Done => deck.get_done().len(),
+                Bucket::Total => deck.ids.len(),
+            })
+            .sum()
+    }
+
+    // unix time at which the next card comes due, 0 if one is due already. None
+    // if no card is scheduled at all.
+    pub fn next_due(&self) -> Option<i64> {
+        let scheduled = self.decks.iter().flat_map(|deck| {
+            deck.status
+                .values()
+                .filter(|status| status.ticks > 0 && !deck.is_done(status))
+        });
+        if scheduled.clone().any(Status::is_due) {
+            return Some(0);
+        }
+        scheduled.map(Status::due_at).min()
+    }
+
+    // for timers outside mnemo: the next due time as by next_due, empty if none.
+    pub fn write_next_due(&self, path: &Path) -> std::io::Result<()> {
+        let contents = self
+            .next_due()
+            .map(|due| format!("{}\n", due))
+            .unwrap_or_default();
+        std::fs::write(path, contents)
+    }
+
+    // one printable html document with a section per deck.
+    pub fn export_study_sheet(&self, w: &mut dyn Write, hide_answers: bool) -> std::io::Result<()> {
+        let title = self
+            .decks
+            .iter()
+            .map(|deck| deck.path.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(w, "<!DOCTYPE html>")?;
+        writeln!(
+            w,
+            "<html><head><meta charset=\"utf-8\"><title>{}</title>",
+            deck::escape_html(&title)
+        )?;
+        writeln!(w, "<style>")?;
+        writeln!(w, "table {{ border-collapse: collapse; }}")?;
+        writeln!(w, "td, th {{ border: 1px solid; padding: 4px 8px; }}")?;
+        writeln!(w, "</style>")?;
+        writeln!(w, "</head><body>")?;
+        for deck in self.decks.iter() {
+            deck.export_study_sheet_section(w, hide_answers)?;
+        }
+        writeln!(w, "</body></html>")?;
+        Ok(())
+    }
+
+    // one aligned row per deck, sorted by `sort_by` with ties in file order.
+    pub fn inspect_table(&self, sort_by: SortBy) -> String {
+        let mut rows = self
+            .decks
+            .iter()
+            .map(|deck| {
+                let counts = [
+                    deck.get_due().len(),
+                    deck.get_new().len(),
+                    deck.get_waiting().len(),
+                    deck.get_done().len(),
+                    deck.ids.len(),
+                ];
+                (deck.path.to_string_lossy().into_owned(), counts)
+            })
+            .collect::<Vec<_>>();
+        let column = match sort_by {
+            SortBy::Due => 0,
+            SortBy::New => 1,
+            SortBy::Total => 4,
+        };
+        rows.sort_by(|(_, a), (_, b)| b[column].cmp(&a[column]));
+
+        let width = rows
+            .iter()
+            .map(|(path, _)| path.len())
+            .max()
+            .unwrap_or(0)
+            .max(4);
+        let mut table = format!(
+            "{:<width$}  {:>5}  {:>5}  {:>7}  {:>5}  {:>5}\n",
+            "deck", "due", "new", "waiting", "done", "total"
+        );
+        for (path, [due, new, waiting, done, total]) in rows {
+            table += &format!(
+                "{:<width$}  {:>5}  {:>5}  {:>7}  {:>5}  {:>5}\n",
+                path, due, new, waiting, done, total
+            );
+        }
+        table
+    }
+
+    // sorted by count, most common first. cards without tags are counted as `(untagged)`.
+    pub fn tag_counts(&self) -> Vec<(String, usize)> {
+        let mut counts = HashMap::<String, usize>::new();
+        for (card, _) in self.decks.iter().flat_map(|deck| deck.iter_cards()) {
+            if card.tags.is_empty() {
+                *counts.entry("(untagged)".to_string()).or_default() += 1;
+            }
+            for tag in card.tags.iter() {
+                *counts.entry(tag.clone()).or_default() += 1;
+            }
+        }
+        let mut counts = counts.into_iter().collect::<Vec<_>>();
+        counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+        counts
+    }
+
+    pub fn get_due(&mut self, opts: &PlayOptions) -> Vec<(usize, usize)> {
+        self.get_due_or_new(
+            |deck| {
+                let mut due = deck.get_due_within(opts.ahead);
+                if opts.due_order == DueOrder::Overdue {
+                    weighted_shuffle(
+                        &mut due,
+                        |id| overdue_weight(&deck.status[id]),
+                        &mut rand::rng(),
+                    );
+                }
+                due
+            },
+            opts.max_old,
+            |deck| deck.config.opts.max_old,
+            opts,
+        )
+    }
+
+    pub fn get_new(&mut self, opts: &PlayOptions) -> Vec<(usize, usize)> {
+        self.get_due_or_new(
+            Deck::get_new,
+            Some(opts.max_new),
+            |deck| new_cap(deck, opts),
+            opts,
+        )
+    }
+
+    // a note for each deck whose last new cards are about to be played, i.e. that
+    // has fewer left than its cap. decks without a cap of their own share
+    // max_new, so they get one note together.
+    pub fn new_shortfalls(&self, opts: &PlayOptions) -> Vec<String> {
+        let note = |name: &str, remaining: usize, requested: usize| {
+            (0 < remaining && remaining < requested).then(|| {
+                let cards = if remaining == 1 {
+                    "card remains"
+                } else {
+                    "cards remain"
+                };
+                format!(
+                    "{}: only {} new {} (requested {}).",
+                    name, remaining, cards, requested
+                )
+            })
+        };
+        let mut notes = vec![];
+        let mut shared = vec![];
+        for deck in self.decks.iter() {
+            let remaining = deck.get_new().len();
+            match new_cap(deck, opts) {
+                Some(cap) => notes.extend(note(&deck.path.to_string_lossy(), remaining, cap)),
+                None => shared.push((deck, remaining)),
+            }
+        }
+        let remaining = shared.iter().map(|(_, remaining)| remaining).sum();
+        let name = match shared.as_slice() {
+            [(deck, _)] => deck.path.to_string_lossy().into_owned(),
+            _ => "all decks".to_string(),
+        };
+        notes.extend(note(&name, remaining, opts.max_new));
+        notes
+    }
+
+    // playlist lines are `deck:id`, with deck paths relative to `dir`. also returns
+    // warnings for lines naming decks or cards that are not in the suite.
+    pub fn parse_playlist(&self, contents: &str, dir: &Path) -> (Vec<(usize, usize)>, Vec<String>) {
+        let decks = self
+            .decks
+            .iter()
+            .map(|deck| deck.path.canonicalize().ok())
+            .collect::<Vec<_>>();
+        let mut playlist = vec![];
+        let mut warnings = vec![];
+        for (i, line) in contents.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let item = line.rsplit_once(':').and_then(|(path, id)| {
+                let path = dir.join(path.trim()).canonicalize().ok()?;
+                let deck_index = decks.iter().position(|p| p.as_ref() == Some(&path))?;
+                let id = id.trim().parse().ok()?;
+                self.decks[deck_index]
+                    .cards
+                    .contains_key(&id)
+                    .then_some((deck_index, id))
+            });
+            match item {
+                Some(item) => playlist.push(item),
+                None => warnings.push(format!("line {}: no card `{}`, skipping", i + 1, line)),
+            }
+        }
+        (playlist, warnings)
+    }
+
+    // moves the `n` learned cards with the highest factor to the front, keeping
+    // their order otherwise.
+    fn warm_up(&self, queue: &mut VecDeque<(usize, usize)>, n: usize) {
+        let factor = |&(deck_index, id): &(usize, usize)| {
+            self.decks[deck_index]
+                .status
+                .get(&id)
+                .map(|status| status.factor)
+                .unwrap_or(0.0)
+        };
+        let mut learned = queue
+            .iter()
+            .copied()
+            .filter(|item| factor(item) > 0.0)
+            .collect::<Vec<_>>();
+        learned.sort_by(|a, b| factor(b).total_cmp(&factor(a)));
+        learned.truncate(n);
+        queue.retain(|item| !learned.contains(item));
+        for item in learned.into_iter().rev() {
+            queue.push_front(item);
+        }
+    }
+
+    // pinned cards over all decks, played first whether due or not.
+    pub fn get_pinned(&self) -> Vec<(usize, usize)> {
+        self.decks
+            .iter()
+            .enumerate()
+            .flat_map(|(deck_index, deck)| {
+                deck.get_pinned()
+                    .into_iter()
+                    .map(move |id| (deck_index, id))
+            })
+            .collect()
+    }
+
+    // for --explain: where card `id` stands in each deck that has it, and why a
+    // session with `opts` would or would not play it. false if no deck has it.
+    pub fn explain(
+        &mut self,
+        id: usize,
+        opts: &PlayOptions,
+        out: &mut dyn Write,
+        date_format: &str,
+    ) -> bool {
+        let queue = self.get_queue(opts);
+        let mut found = false;
+        for (deck_index, deck) in self.decks.iter().enumerate() {
+            let Some(card) = deck.cards.get(&id) else {
+                continue;
+            };
+            found = true;
+            writeln!(
+                out,
+                "{}::#{}: {}",
+                deck.path.to_string_lossy().green(),
+                id,
+                card.answer
+            )
+            .unwrap();
+
+            let status = deck.status.get(&id).copied().unwrap_or(Status::new(id));
+            let bucket = if status.is_new() {
+                "new"
+            } else if deck.is_done(&status) {
+                "done"
+            } else if status.is_due() {
+                "due"
+            } else {
+                "waiting"
+            };
+            writeln!(out, "{}", bucket).unwrap();
+            if !status.is_new() {
+                writeln!(
+                    out,
+                    "due {} ({} days left), factor {:.2}, {} ticks",
+                    deck::format_date(status.due_date(), date_format),
+                    status.days_left(),
+                    status.factor,
+                    status.ticks
+                )
+                .unwrap();
+            }
+
+            let reason = if card.is_pinned() {
+                "played first, as it is pinned.".to_string()
+            } else if queue.contains(&(deck_index, id)) {
+                "in today's queue.".to_string()
+            } else if opts.playlist.is_some() {
+                "not in the playlist.".to_string()
+            } else if bucket == "new" {
+                "not played: earlier new cards take up the new card cap.".to_string()
+            } else if bucket == "done" {
+                "not played: done cards are only reviewed with --review-done.".to_string()
+            } else if opts.new_only {
+                "not played: --new-only leaves out reviews.".to_string()
+            } else if status.is_due_within(opts.ahead) {
+                "not played: earlier due cards take up the review cap.".to_string()
+            } else {
+                format!(
+                    "not played: not due until {}.",
+                    deck::format_date(status.due_date(), date_format)
+                )
+            };
+            writeln!(out, "{}", reason).unwrap();
+        }
+        found
+    }
+
+    // done cards over all decks with the unix time they became done, earliest
+    // first. that is taken to be their last review, one interval before they are due.
+    pub fn list_done(&self) -> Vec<(usize, usize, i64)> {
+        let mut done = self
+            .decks
+            .iter()
+            .enumerate()
+            .flat_map(|(deck_index, deck)| {
+                deck.get_done().into_iter().map(move |id| {
+                    let status = &deck.status[&id];
+                    let graduated = status.timestamp - (86400.0 * status.factor) as i64;
+                    (deck_index, id, graduated)
+                })
+            })
+            .collect::<Vec<_>>();
+        done.sort_by_key(|&(_, _, graduated)| graduated);
+        done
+    }
+
+    // `n` random done cards over all decks, for --review-done.
+    pub fn sample_done<R: Rng>(&self, n: usize, rng: &mut R) -> Vec<(usize, usize)> {
+        let mut done = self
+            .decks
+            .iter()
+            .enumerate()
+            .flat_map(|(deck_index, deck)| {
+                deck.get_done().into_iter().map(move |id| (deck_index, id))
+            })
+            .collect::<Vec<_>>();
+        done.shuffle(rng);
+        done.truncate(n);
+        done
+    }
+
+    // the `n` learned cards, over all decks, that come due soonest after the ones
+    // already due.
+    pub fn get_upcoming(&self, n: usize, opts: &PlayOptions) -> Vec<(usize, usize)> {
+        let mut upcoming = self
+            .decks
+            .iter()
+            .enumerate()
+            .flat_map(|(deck_index, deck)| {
+                deck.get_upcoming(opts.ahead)
+                    .into_iter()
+                    .map(move |id| (deck.status[&id].timestamp, deck_index, id))
+            })
+            .collect::<Vec<_>>();
+        upcoming.sort();
+        upcoming
+            .into_iter()
+            .take(n)
+            .map(|(_, deck_index, id)| (deck_index, id))
+            .collect()
+    }
+}
+
+// the deck's own cap on new cards, if it has one. otherwise it shares max_new.
+pub fn new_cap(deck: &Deck, opts: &PlayOptions) -> Option<usize> {
+    opts.new_percent
+        .map(|percent| percent_of(percent, deck.ids.len()))
+        .or(deck.config.opts.new_cards)
+}
+
+// at least 1, so that small decks still get new cards.
+fn percent_of(percent: f64, total: usize) -> usize {
+    ((percent / 100.0 * total as f64).round() as usize).max(1)
+}
+
+// one new card after every `k` due ones. whatever is left of either comes last.
+fn interleave<T>(due: Vec<T>, new: Vec<T>, k: usize) -> VecDeque<T> {
+    let mut queue = VecDeque::with_capacity(due.len() + new.len());
+    let mut new = new.into_iter();
+    for (i, item) in due.into_iter().enumerate() {
+        queue.push_back(item);
+        if (i + 1) % k.max(1) == 0 {
+            queue.extend(new.next());
+        }
+    }
+    queue.extend(new);
+    queue
+}
+
+// reading answers from a pipe would grade every card on eof.
+fn check_interactive(opts: &PlayOptions, stdin_is_terminal: bool) -> Result<(), PlayErr> {
+    if stdin_is_terminal || opts.non_interactive {
+        Ok(())
+    } else {
+        Err(PlayErr::NotInteractive)
+    }
+}
+
+// a card due today weighs 1, and one more for every day overdue.
+fn overdue_weight(status: &Status) -> f64 {
+    (1 - status.days_left()).max(1) as f64
+}
+
+// a random order where heavier items tend to come first: each item is drawn
+// with probability proportional to its weight among those left.
+fn weighted_shuffle<T, F, R>(v: &mut Vec<T>, weight: F, rng: &mut R)
+where
+    F: Fn(&T) -> f64,
+    R: Rng,
+{
+    let mut keyed = v
+        .drain(..)
+        .map(|item| (rng.random::<f64>().powf(1.0 / weight(&item)), item))
+        .collect::<Vec<_>>();
+    keyed.sort_by(|a, b| b.0.total_cmp(&a.0));
+    v.extend(keyed.into_iter().map(|(_, item)| item));
+}
+
+// swaps each card with one of the next `window - 1` cards, so a card never
+// moves more than `window - 1` places towards the front.
+fn jitter<T, R: Rng>(v: &mut [T], window: usize, rng: &mut R) {
+    for i in 0..v.len() {
+        let j = rng.random_range(i..v.len().min(i + window.max(1)));
+        v.swap(i, j);
+    }
+}
+
+// puts a card that is still due back into the queue. without a gap it goes to
+// the back, otherwise it comes back after `gap` other cards (or last, if fewer remain).
+fn requeue<T>(queue: &mut VecDeque<T>, item: T, gap: Option<usize>) {
+    match gap {
+        Some(gap) if gap < queue.len() => queue.insert(gap, item),
+        _ => queue.push_back(item),
     }
+}
 
-    pub fn get_new(&mut self, max: Option<usize>, randomize: bool) -> Vec<(usize, usize)> {
-        self.get_due_or_new(Deck::get_new, max, randomize)
+// parses 1-based deck numbers separated by spaces or commas into 0-based indices.
+fn parse_selection(s: &str, len: usize) -> Option<Vec<usize>> {
+    s.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|n| !n.is_empty())
+        .map(|n| match n.parse::<usize>() {
+            Ok(n) if (1..=len).contains(&n) => Some(n - 1),
+            _ => None,
+        })
+        .collect()
+}
+
+// `--inspect --format minimal`, e.g. `12d/5n`, for status bars.
+pub fn format_minimal((due, new): (usize, usize)) -> String {
+    format!("{}d/{}n", due, new)
+}
+
+// `visited` holds the canonical paths of the directories searched so far, so that
+// symlinks looping back to one of them are not followed again.
+fn find_decks(
+    dir: &Path,
+    recursive: bool,
+    visited: &mut HashSet<PathBuf>,
+    decks: &mut Vec<PathBuf>,
+) -> std::io::Result<()> {
+    if !visited.insert(dir.canonicalize()?) {
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if recursive {
+                find_decks(&path, recursive, visited, decks)?;
+            }
+        } else if path.extension().and_then(|s| s.to_str()) == Some("mnemo") {
+            decks.push(path);
+        }
     }
+    Ok(())
+}
+
+// a suite given where one deck file is needed, e.g. for --add-cards.
+pub fn find_suite(files: &[PathBuf]) -> Option<&PathBuf> {
+    files
+        .iter()
+        .find(|path| path.extension().and_then(|s| s.to_str()) == Some("suite"))
 }
 
-pub fn parse_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>, (PathBuf, DeckErr)> {
+// expands suites into their decks. several suites make up one session, and a deck
+// listed more than once, e.g. in two suites, is only played once.
+// a directory stands for the .mnemo files in it, and with `recursive` in its
+// subdirectories too, sorted by path.
+pub fn parse_files(paths: &[PathBuf], recursive: bool) -> Result<Vec<PathBuf>, (PathBuf, DeckErr)> {
     let mut ret = vec![];
     for path in paths.iter() {
-        if path.extension().and_then(|s| s.to_str()) == Some("suite") {
+        if path.is_dir() {
+            let mut decks = vec![];
+            find_decks(path, recursive, &mut HashSet::new(), &mut decks)
+                .map_err(|_| (path.clone(), DeckErr::FileNotFound))?;
+            if decks.is_empty() {
+                return Err((path.clone(), DeckErr::NoDecksInDirectory));
+            }
+            decks.sort();
+            ret.extend(decks);
+        } else if path.extension().and_then(|s| s.to_str()) == Some("suite") {
             let dir = path.parent().unwrap_or_else(|| Path::new(""));
             let suite_contents =
                 std::fs::read_to_string(path).map_err(|_| (path.clone(), DeckErr::FileNotFound))?;
@@ -162,5 +1027,1093 @@ pub fn parse_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>, (PathBuf, DeckErr)
             ret.push(path.clone());
         }
     }
+    let mut seen = HashSet::new();
+    ret.retain(|path| seen.insert(path.canonicalize().unwrap_or_else(|_| path.clone())));
     Ok(ret)
 }
+
+#[cfg(test)]
+mod test_suite {
+    use super::*;
+
+    fn played_ids(path: &str, opts: &PlayOptions, answers: &str) -> Vec<usize> {
+        let mut suite = Suite::read_from_files(&[PathBuf::from(path)], None).unwrap();
+        let mut out = vec![];
+        suite.play_with_clock(opts, &mut answers.as_bytes(), &mut out, &mut || {
+            Duration::ZERO
+        });
+        String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .filter_map(|line| line.split_once("::#"))
+            .map(|(_, id)| id.parse().unwrap())
+            .collect()
+    }
+
+    // runs whole sessions with scripted answers on decks given as text, so nothing
+    // is read from or written to disk.
+    struct SessionHarness {
+        suite: Suite,
+    }
+
+    struct Session {
+        out: String,
+        // each deck's log as it would be saved after the session.
+        logs: Vec<HashMap<usize, Status>>,
+        // (right, played) per deck.
+        scores: Vec<(usize, usize)>,
+    }
+
+    impl SessionHarness {
+        // (deck, log) pairs.
+        fn new(decks: &[(&str, &str)]) -> SessionHarness {
+            let decks = decks
+                .iter()
+                .map(|(deck, log)| {
+                    let mut deck = Deck::read_from_reader(&mut deck.as_bytes()).unwrap();
+                    for (_, status) in crate::deck::parse_log(log).unwrap() {
+                        deck.set_status(status.id, status);
+                    }
+                    deck
+                })
+                .collect();
+            SessionHarness {
+                suite: Suite { decks },
+            }
+        }
+
+        fn run(&mut self, opts: &PlayOptions, answers: &[&str]) -> Session {
+            let answers = answers
+                .iter()
+                .map(|a| format!("{}\n", a))
+                .collect::<String>();
+            let mut out = vec![];
+            self.suite
+                .play_with_clock(opts, &mut answers.as_bytes(), &mut out, &mut || {
+                    Duration::ZERO
+                });
+            let decks = self.suite.decks.iter();
+            Session {
+                out: String::from_utf8(out).unwrap(),
+                logs: decks
+                    .clone()
+                    .map(|deck| crate::deck::parse_log(&deck.log_contents()).unwrap())
+                    .collect(),
+                scores: decks
+                    .map(|deck| (deck.played.len() - deck.wrong.len(), deck.played.len()))
+                    .collect(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_session_correct_then_wrong() {
+        let mut harness = SessionHarness::new(&[(
+            "1 | Stockholm | Sweden\n2 | Oslo | Norway\n",
+            "1,100000000,4.00\n2,200000000,4.00\n",
+        )]);
+        let session = harness.run(&PlayOptions::default(), &["", "y", "", "n", "", "y"]);
+        assert_eq!(session.scores, vec![(1, 2)]);
+        assert!(session.out.contains("got 1 wrong:\n2: Oslo"));
+
+        let log = &session.logs[0];
+        // right: a longer interval, wrong: a shorter one.
+        assert!(log[&1].factor > 4.0);
+        assert_eq!((log[&1].reviews, log[&1].lapses), (1, 0));
+        assert!(log[&2].factor < 4.0);
+        assert_eq!((log[&2].reviews, log[&2].lapses), (0, 1));
+    }
+
+    #[test]
+    fn test_new_shortfalls() {
+        let opts = |max_new| PlayOptions {
+            max_new,
+            ..PlayOptions::default()
+        };
+        let tags = Suite::read_from_files(&[PathBuf::from("tests/test_tags.mnemo")], None).unwrap();
+        assert_eq!(
+            tags.new_shortfalls(&opts(10)),
+            vec!["tests/test_tags.mnemo: only 4 new cards remain (requested 10)."]
+        );
+        assert!(tags.new_shortfalls(&opts(4)).is_empty());
+
+        let paths = ["tests/test_tags.mnemo", "tests/test_pin.mnemo"].map(PathBuf::from);
+        let suite = Suite::read_from_files(&paths, None).unwrap();
+        assert!(suite.new_shortfalls(&opts(5)).is_empty());
+        assert_eq!(
+            suite.new_shortfalls(&opts(10)),
+            vec!["all decks: only 6 new cards remain (requested 10)."]
+        );
+
+        // nothing left at all is not worth a note every session.
+        let suite =
+            Suite::read_from_files(&[PathBuf::from("tests/test_all_done.mnemo")], None).unwrap();
+        assert!(suite.new_shortfalls(&opts(10)).is_empty());
+
+        let mut harness = SessionHarness::new(&[("1 | Stockholm | Sweden\n", "")]);
+        let session = harness.run(&opts(20), &["", "y"]);
+        assert!(session
+            .out
+            .starts_with("<stdin>: only 1 new card remains (requested 20).\n"));
+    }
+
+    #[test]
+    fn test_session_new_card() {
+        let mut harness = SessionHarness::new(&[("1 | Stockholm | Sweden\n", "")]);
+        let opts = PlayOptions {
+            max_new: 1,
+            ..PlayOptions::default()
+        };
+        // through both learning steps, failing the second one once.
+        let session = harness.run(&opts, &["", "y", "", "n", "", "y", "", "y"]);
+        assert_eq!(session.scores, vec![(1, 1)]);
+        let log = &session.logs[0];
+        // graduated: learned, with the first interval.
+        assert!((1.0..=1.2).contains(&log[&1].factor));
+        assert_eq!(log[&1].step, 0);
+    }
+
+    #[test]
+    fn test_caught_up() {
+        const DECK_COPY: &str = "tests/test_caught_up_copy.mnemo";
+        const LOG_COPY: &str = "tests/test_caught_up_copy.mnemo.log";
+        std::fs::copy("tests/test_all_done.mnemo", DECK_COPY).unwrap();
+        std::fs::copy("tests/test_all_done.mnemo.log", LOG_COPY).unwrap();
+        let modified = || std::fs::metadata(LOG_COPY).unwrap().modified().unwrap();
+        let before = modified();
+
+        let mut suite = Suite::read_from_files(&[PathBuf::from(DECK_COPY)], None).unwrap();
+        let opts = PlayOptions {
+            max_new: 10,
+            ..PlayOptions::default()
+        };
+        let mut out = vec![];
+        suite.play_with_clock(&opts, &mut "".as_bytes(), &mut out, &mut || Duration::ZERO);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "nothing to review, you're all caught up!\n"
+        );
+        assert_eq!(modified(), before);
+        assert_eq!(
+            std::fs::read_to_string(LOG_COPY).unwrap(),
+            std::fs::read_to_string("tests/test_all_done.mnemo.log").unwrap()
+        );
+
+        std::fs::remove_file(DECK_COPY).unwrap();
+        std::fs::remove_file(LOG_COPY).unwrap();
+    }
+
+    #[test]
+    fn test_transcript() {
+        const TRANSCRIPT: &str = "tests/test_transcript_copy.txt";
+        let _ = std::fs::remove_file(TRANSCRIPT);
+        let mut harness = SessionHarness::new(&[(
+            "0 | Capital | Country | Founded\n1 | Stockholm | Sweden |\n2 | Oslo | Norway | 1040\n",
+            "1,100000000,4.00\n2,200000000,4.00\n",
+        )]);
+        let opts = PlayOptions {
+            transcript: Some(PathBuf::from(TRANSCRIPT)),
+            ..PlayOptions::default()
+        };
+        harness.run(&opts, &["", "y", "", "n"]);
+
+        // without the date and time.
+        let transcript = std::fs::read_to_string(TRANSCRIPT).unwrap();
+        let lines = transcript
+            .lines()
+            .map(|line| line.splitn(3, ' ').nth(2).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("<stdin>::#1: Sweden -> Stockholm: ok, due in "));
+        assert!(lines[1].starts_with("<stdin>::#2: Norway / 1040 -> Oslo: failed, due in "));
+        std::fs::remove_file(TRANSCRIPT).unwrap();
+    }
+
+    #[test]
+    fn test_conceal_progress() {
+        let mut harness = SessionHarness::new(&[(
+            "1 | Stockholm | Sweden\n2 | Oslo | Norway\n",
+            "1,100000000,4.00\n",
+        )]);
+        let opts = PlayOptions {
+            max_new: 1,
+            conceal_number: true,
+            conceal_progress: true,
+            ..PlayOptions::default()
+        };
+        let session = harness.run(&opts, &["", "y", "", "y", "", "y"]);
+        let banners = session
+            .out
+            .lines()
+            .filter(|line| line.starts_with("<stdin>::"))
+            .collect::<Vec<_>>();
+        // the new card comes back for its second learning step.
+        assert_eq!(
+            banners,
+            vec![
+                "<stdin>::card 1 of 2",
+                "<stdin>::card 2 of 2",
+                "<stdin>::card 3 of 3"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_relearn_gap() {
+        const DECK_COPY: &str = "tests/test_relearn_gap_copy.mnemo";
+        let answers = "\ny\n".repeat(10);
+
+        std::fs::copy("tests/test_relearn_gap.mnemo", DECK_COPY).unwrap();
+        let opts = PlayOptions {
+            max_new: 5,
+            ..PlayOptions::default()
+        };
+        assert_eq!(
+            played_ids(DECK_COPY, &opts, &answers),
+            vec![1, 2, 3, 4, 5, 1, 2, 3, 4, 5]
+        );
+
+        std::fs::copy("tests/test_relearn_gap.mnemo", DECK_COPY).unwrap();
+        std::fs::remove_file(format!("{}.log", DECK_COPY)).unwrap();
+        let opts = PlayOptions {
+            max_new: 5,
+            relearn_gap: Some(2),
+            ..PlayOptions::default()
+        };
+        assert_eq!(
+            played_ids(DECK_COPY, &opts, &answers),
+            vec![1, 2, 3, 1, 2, 3, 4, 5, 4, 5]
+        );
+
+        std::fs::remove_file(DECK_COPY).unwrap();
+        std::fs::remove_file(format!("{}.log", DECK_COPY)).unwrap();
+    }
+
+    #[test]
+    fn test_minutes_per_deck() {
+        // 9 cards over 90 seconds: 40 seconds for the 4 cards of test_tags, 50 for
+        // the 5 of test_parse_ok. every card takes 20 seconds.
+        let mut suite = Suite::read_from_files(
+            &[
+                PathBuf::from("tests/test_tags.mnemo"),
+                PathBuf::from("tests/test_parse_ok.mnemo"),
+            ],
+            None,
+        )
+        .unwrap();
+        let opts = PlayOptions {
+            max_new: 10,
+            preview: true,
+            minutes_per_deck: Some(1.5),
+            ..PlayOptions::default()
+        };
+        let mut now = Duration::ZERO;
+        let mut clock = || {
+            now += Duration::from_secs(20);
+            now
+        };
+        let mut out = vec![];
+        suite.play_with_clock(&opts, &mut "\n".repeat(9).as_bytes(), &mut out, &mut clock);
+        let played = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .filter_map(|line| line.split_once("::#"))
+            .map(|(path, id)| format!("{}#{}", path.trim_start_matches("tests/test_"), id))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            played,
+            vec![
+                "parse_ok.mnemo#1",
+                "parse_ok.mnemo#2",
+                "parse_ok.mnemo#3",
+                "tags.mnemo#1",
+                "tags.mnemo#2",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_min_session() {
+        const DECK_COPY: &str = "tests/test_min_session_copy.mnemo";
+        const LOG_COPY: &str = "tests/test_min_session_copy.mnemo.log";
+        std::fs::copy("tests/test_min_session.mnemo", DECK_COPY).unwrap();
+        std::fs::copy("tests/test_min_session.mnemo.log", LOG_COPY).unwrap();
+
+        let opts = PlayOptions {
+            min_session: Some(5),
+            ..PlayOptions::default()
+        };
+        let suite = Suite::read_from_files(&[PathBuf::from(DECK_COPY)], None).unwrap();
+        assert_eq!(suite.get_upcoming(3, &opts), vec![(0, 5), (0, 3), (0, 6)]);
+        assert_eq!(
+            played_ids(DECK_COPY, &opts, &"\ny\n".repeat(5)),
+            vec![1, 2, 5, 3, 6]
+        );
+
+        std::fs::remove_file(DECK_COPY).unwrap();
+        std::fs::remove_file(LOG_COPY).unwrap();
+    }
+
+    #[test]
+    fn test_new_only() {
+        const DECK_COPY: &str = "tests/test_new_only_copy.mnemo";
+        const LOG_COPY: &str = "tests/test_new_only_copy.mnemo.log";
+        std::fs::copy("tests/test_parse_ok.mnemo", DECK_COPY).unwrap();
+        std::fs::copy("tests/test_parse_ok.mnemo.log", LOG_COPY).unwrap();
+
+        let opts = PlayOptions {
+            max_new: 1,
+            new_only: true,
+            ..PlayOptions::default()
+        };
+        assert_eq!(
+            played_ids(DECK_COPY, &opts, &"\ny\n".repeat(10)),
+            vec![4, 4]
+        );
+        let opts = PlayOptions {
+            max_new: 1,
+            ..PlayOptions::default()
+        };
+        assert_eq!(played_ids(DECK_COPY, &opts, "\ny\n")[0], 1);
+
+        std::fs::remove_file(DECK_COPY).unwrap();
+        std::fs::remove_file(LOG_COPY).unwrap();
+    }
+
+    #[test]
+    fn test_autosave_every() {
+        const DECK_COPY: &str = "tests/test_autosave_every_copy.mnemo";
+        const LOG_COPY: &str = "tests/test_autosave_every_copy.mnemo.log";
+        std::fs::copy("tests/test_priority.mnemo", DECK_COPY).unwrap();
+        let _ = std::fs::remove_file(LOG_COPY);
+        let logged = || {
+            std::fs::read_to_string(LOG_COPY)
+                .unwrap_or_default()
+                .lines()
+                .count()
+        };
+
+        // new cards need two passes, so the first answers finish nothing.
+        let opts = PlayOptions {
+            max_new: 5,
+            ..PlayOptions::default()
+        };
+        played_ids(DECK_COPY, &opts, "\ny\n\ny\nq\n");
+        assert_eq!(logged(), 0);
+
+        let opts = PlayOptions {
+            autosave_every: Some(2),
+            ..opts
+        };
+        played_ids(DECK_COPY, &opts, "\ny\nq\n");
+        assert_eq!(logged(), 0);
+        played_ids(DECK_COPY, &opts, "\ny\n\ny\nq\n");
+        assert_eq!(logged(), 2);
+        // 3 and 5 finish, 1 and 4 are saved on the 4th answer, 2 on none.
+        played_ids(DECK_COPY, &opts, "\ny\n".repeat(5).as_str());
+        assert_eq!(logged(), 4);
+
+        std::fs::remove_file(DECK_COPY).unwrap();
+        std::fs::remove_file(LOG_COPY).unwrap();
+    }
+
+    #[test]
+    fn test_review_done() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let suite = Suite::read_from_files(
+            &[
+                PathBuf::from("tests/test_review_done.mnemo"),
+                PathBuf::from("tests/test_parse_ok.mnemo"),
+            ],
+            None,
+        )
+        .unwrap();
+        for seed in 0..20 {
+            let mut done = suite.sample_done(2, &mut StdRng::seed_from_u64(seed));
+            assert_eq!(done.len(), 2);
+            done.sort();
+            done.dedup();
+            assert_eq!(done.len(), 2);
+            assert!(done
+                .iter()
+                .all(|item| [(0, 1), (0, 2), (0, 4)].contains(item)));
+        }
+        assert_eq!(
+            suite.sample_done(10, &mut StdRng::seed_from_u64(0)).len(),
+            3
+        );
+
+        const DECK_COPY: &str = "tests/test_review_done_copy.mnemo";
+        const LOG_COPY: &str = "tests/test_review_done_copy.mnemo.log";
+        std::fs::copy("tests/test_review_done.mnemo", DECK_COPY).unwrap();
+        std::fs::copy("tests/test_review_done.mnemo.log", LOG_COPY).unwrap();
+        let opts = PlayOptions {
+            review_done: 1,
+            ..PlayOptions::default()
+        };
+        let played = played_ids(DECK_COPY, &opts, &"\ny\n".repeat(5));
+        assert_eq!(played.len(), 2);
+        assert_eq!(played[0], 3);
+        assert!([1, 2, 4].contains(&played[1]));
+        std::fs::remove_file(DECK_COPY).unwrap();
+        std::fs::remove_file(LOG_COPY).unwrap();
+    }
+
+    #[test]
+    fn test_new_percent() {
+        const DECK: &str = "tests/test_new_percent_copy.mnemo";
+        let contents = (1..=2000)
+            .map(|id| format!("{} | answer {} | cue {}\n", id, id, id))
+            .collect::<String>();
+        std::fs::write(DECK, contents).unwrap();
+        let mut suite = Suite::read_from_files(
+            &[
+                PathBuf::from(DECK),
+                PathBuf::from("tests/test_parse_ok.mnemo"),
+            ],
+            None,
+        )
+        .unwrap();
+        let opts = PlayOptions {
+            max_new: 5,
+            new_percent: Some(1.0),
+            ..PlayOptions::default()
+        };
+        let new = suite.get_new(&opts);
+        assert_eq!(new.iter().filter(|(deck, _)| *deck == 0).count(), 20);
+        assert_eq!(new.iter().filter(|(deck, _)| *deck == 1).count(), 1);
+
+        assert_eq!(percent_of(1.0, 2000), 20);
+        assert_eq!(percent_of(2.5, 100), 3);
+        assert_eq!(percent_of(1.0, 20), 1);
+        std::fs::remove_file(DECK).unwrap();
+    }
+
+    #[test]
+    fn test_grade_answers() {
+        colored::control::set_override(false);
+        let deck = Deck::read_from_reader(
+            &mut "# strip_parens: true\n1 | run (verb) | correr\n2 | eat | comer\n3 | sleep | dormir\n"
+                .as_bytes(),
+        )
+        .unwrap();
+        let mut suite = Suite { decks: vec![deck] };
+        let opts = PlayOptions {
+            max_new: 10,
+            ..PlayOptions::default()
+        };
+        let mut out = vec![];
+        let score = suite.grade_answers(&opts, "Run\ndrink\n", &mut out);
+        assert_eq!(score, (1, 3));
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("#1: run (verb) ok."));
+        assert!(out.contains("#2: eat failed. (answered `drink`)"));
+        assert!(out.contains("#3: sleep failed. (answered ``)"));
+        assert!(out.ends_with("score: 1/3.\n"));
+        // nothing was scheduled.
+        assert!(suite.decks[0].status.is_empty());
+
+        let mut out = vec![];
+        assert_eq!(
+            suite.grade_answers(&opts, "run\neat\nsleep\nextra\n", &mut out),
+            (3, 3)
+        );
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .contains("unused answers: 1."));
+    }
+
+    #[test]
+    fn test_interleave() {
+        let due = |n: usize| (1..=n).collect::<Vec<_>>();
+        let new = |n: usize| (101..=100 + n).collect::<Vec<_>>();
+        assert_eq!(
+            interleave(due(5), new(3), 2),
+            vec![1, 2, 101, 3, 4, 102, 5, 103]
+        );
+        assert_eq!(interleave(due(4), new(1), 1), vec![1, 101, 2, 3, 4]);
+        assert_eq!(interleave(due(0), new(2), 3), vec![101, 102]);
+        assert_eq!(interleave(due(3), new(0), 1), vec![1, 2, 3]);
+        assert_eq!(interleave(due(2), new(2), 0), vec![1, 101, 2, 102]);
+    }
+
+    #[test]
+    fn test_warmup() {
+        const DECK_COPY: &str = "tests/test_warmup_copy.mnemo";
+        const LOG_COPY: &str = "tests/test_warmup_copy.mnemo.log";
+        std::fs::copy("tests/test_parse_ok.mnemo", DECK_COPY).unwrap();
+        for (warmup, played) in [
+            (0, vec![1, 2, 3, 4, 5]),
+            (2, vec![3, 2, 1, 4, 5]),
+            (10, vec![3, 2, 1, 4, 5]),
+        ] {
+            std::fs::copy("tests/test_parse_ok.mnemo.log", LOG_COPY).unwrap();
+            let opts = PlayOptions {
+                max_new: 2,
+                warmup,
+                ..PlayOptions::default()
+            };
+            assert_eq!(played_ids(DECK_COPY, &opts, &"\ny\n".repeat(4)), played);
+        }
+        std::fs::remove_file(DECK_COPY).unwrap();
+        std::fs::remove_file(LOG_COPY).unwrap();
+    }
+
+    #[test]
+    fn test_pinned_first() {
+        let mut suite =
+            Suite::read_from_files(&[PathBuf::from("tests/test_pin.mnemo")], None).unwrap();
+        let opts = PlayOptions {
+            max_new: 1,
+            ..PlayOptions::default()
+        };
+        // 4 is not due and 5 is new, but both are pinned.
+        let queue = suite.get_queue(&opts).into_iter().collect::<Vec<_>>();
+        assert_eq!(queue, vec![(0, 4), (0, 5), (0, 1), (0, 2), (0, 3)]);
+    }
+
+    #[test]
+    fn test_playlist() {
+        let suite = Suite::read_from_files(
+            &[
+                PathBuf::from("tests/test_parse_ok.mnemo"),
+                PathBuf::from("tests/test_tags.mnemo"),
+            ],
+            None,
+        )
+        .unwrap();
+        let (playlist, warnings) = suite.parse_playlist(
+            "test_tags.mnemo:3\n\
+             ./test_parse_ok.mnemo:5\n\
+             test_parse_ok.mnemo:42\n\
+             test_other.mnemo:1\n\
+             \n\
+             test_parse_ok.mnemo: 1\n",
+            Path::new("tests"),
+        );
+        assert_eq!(playlist, vec![(1, 3), (0, 5), (0, 1)]);
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].starts_with("line 3:"));
+
+        // not due, or done playing, cards are still played in order.
+        let opts = PlayOptions {
+            preview: true,
+            playlist: Some(vec![(0, 5), (0, 1), (0, 5), (0, 4)]),
+            ..PlayOptions::default()
+        };
+        assert_eq!(
+            played_ids("tests/test_parse_ok.mnemo", &opts, &"\n".repeat(4)),
+            vec![5, 1, 5, 4]
+        );
+
+        let mut suite = Suite {
+            decks: vec![Deck::read_from_reader(&mut "1 | a | A\n".as_bytes()).unwrap()],
+        };
+        let opts = PlayOptions {
+            playlist: Some(vec![(0, 1); 3]),
+            non_interactive: true,
+            ..PlayOptions::default()
+        };
+        let mut out = vec![];
+        suite
+            .play(&opts, &mut "\ny\n".repeat(3).as_bytes(), &mut out)
+            .unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().matches("ok.").count(), 3);
+        // graduating, then one review.
+        assert_eq!(suite.decks[0].status[&1].reviews, 2);
+    }
+
+    #[test]
+    fn test_deck_opts() {
+        let deck = |contents: &str| Deck::read_from_reader(&mut contents.as_bytes()).unwrap();
+        let cards = "1 | a | A\n2 | b | B\n3 | c | C\n4 | d | D\n";
+        let opts = PlayOptions {
+            max_new: 3,
+            ..PlayOptions::default()
+        };
+
+        let mut suite = Suite {
+            decks: vec![deck(&format!("# opt: new_cards=2\n{}", cards))],
+        };
+        assert_eq!(suite.get_new(&opts), vec![(0, 1), (0, 2)]);
+        let mut suite = Suite {
+            decks: vec![deck(&format!("# opt: new_cards=4\n{}", cards))],
+        };
+        assert_eq!(suite.get_new(&opts).len(), 4);
+
+        // the capped deck doesn't use up the suite-wide cap of the other.
+        let mut suite = Suite {
+            decks: vec![deck(cards), deck(&format!("# opt: new_cards=1\n{}", cards))],
+        };
+        assert_eq!(suite.get_new(&opts), vec![(0, 1), (1, 1), (0, 2), (0, 3)]);
+
+        // a randomized capped deck picks from all of its new cards.
+        let mut suite = Suite {
+            decks: vec![deck(&format!(
+                "# opt: new_cards=1\n# opt: randomize\n{}",
+                cards
+            ))],
+        };
+        let picked = (0..100)
+            .map(|_| suite.get_new(&opts)[0].1)
+            .collect::<HashSet<_>>();
+        assert!(picked.len() > 1);
+    }
+
+    #[test]
+    fn test_check_interactive() {
+        let opts = PlayOptions::default();
+        assert_eq!(check_interactive(&opts, true), Ok(()));
+        assert_eq!(
+            check_interactive(&opts, false),
+            Err(PlayErr::NotInteractive)
+        );
+        let opts = PlayOptions {
+            non_interactive: true,
+            ..PlayOptions::default()
+        };
+        assert_eq!(check_interactive(&opts, false), Ok(()));
+
+        let mut suite =
+            Suite::read_from_files(&[PathBuf::from("tests/test_parse_ok.mnemo")], None).unwrap();
+        let mut out = vec![];
+        if !std::io::stdin().is_terminal() {
+            assert_eq!(
+                suite.play(&PlayOptions::default(), &mut "".as_bytes(), &mut out),
+                Err(PlayErr::NotInteractive)
+            );
+            assert!(out.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_preview() {
+        const DECK_COPY: &str = "tests/test_preview_copy.mnemo";
+        const LOG_COPY: &str = "tests/test_preview_copy.mnemo.log";
+        std::fs::copy("tests/test_parse_ok.mnemo", DECK_COPY).unwrap();
+        std::fs::copy("tests/test_parse_ok.mnemo.log", LOG_COPY).unwrap();
+
+        let opts = PlayOptions {
+            max_new: 10,
+            preview: true,
+            non_interactive: true,
+            ..PlayOptions::default()
+        };
+        let mut suite = Suite::read_from_files(&[PathBuf::from(DECK_COPY)], None).unwrap();
+        let mut out = vec![];
+        suite
+            .play(&opts, &mut "\n".repeat(5).as_bytes(), &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.matches("::#").count(), 5);
+        assert!(out.contains("Capital: Mogadishu"));
+        assert!(!out.contains("correct?"));
+
+        assert_eq!(
+            std::fs::read_to_string(LOG_COPY).unwrap(),
+            std::fs::read_to_string("tests/test_parse_ok.mnemo.log").unwrap()
+        );
+        assert!(suite.decks[0].played.is_empty());
+        assert!(!suite.decks[0].status.contains_key(&4));
+
+        std::fs::remove_file(DECK_COPY).unwrap();
+        std::fs::remove_file(LOG_COPY).unwrap();
+    }
+
+    #[test]
+    fn test_jitter() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let sorted = (0..20).collect::<Vec<_>>();
+        let mut changed = false;
+        for seed in 0..100 {
+            let mut v = sorted.clone();
+            jitter(&mut v, 4, &mut StdRng::seed_from_u64(seed));
+            assert!(v[0] < 4);
+            for (i, &card) in v.iter().enumerate() {
+                assert!(card < i + 4);
+            }
+            let mut check = v.clone();
+            check.sort();
+            assert_eq!(check, sorted);
+            changed |= v != sorted;
+        }
+        assert!(changed);
+
+        let mut v = sorted.clone();
+        jitter(&mut v, 1, &mut StdRng::seed_from_u64(0));
+        assert_eq!(v, sorted);
+    }
+
+    #[test]
+    fn test_weighted_shuffle() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let status = |days_overdue: i64| Status {
+            timestamp: chrono::Local::now().timestamp() - days_overdue * 86400,
+            factor: 1.0,
+            ticks: 1,
+            ..Status::new(1)
+        };
+        assert_eq!(overdue_weight(&status(30)), 31.0);
+        assert_eq!(overdue_weight(&status(0)), 1.0);
+
+        // 30 days overdue (31) against 1 day overdue (2).
+        let statuses = [status(1), status(30)];
+        let mut first = 0;
+        for seed in 0..1000 {
+            let mut v = vec![0, 1];
+            weighted_shuffle(
+                &mut v,
+                |&i| overdue_weight(&statuses[i]),
+                &mut StdRng::seed_from_u64(seed),
+            );
+            if v[0] == 1 {
+                first += 1;
+            }
+        }
+        assert!(first > 900, "{}", first);
+        assert!(first < 1000);
+
+        let mut v = (0..10).collect::<Vec<_>>();
+        weighted_shuffle(&mut v, |_| 1.0, &mut StdRng::seed_from_u64(0));
+        v.sort();
+        assert_eq!(v, (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_parse_selection() {
+        assert_eq!(parse_selection("1 3", 3), Some(vec![0, 2]));
+        assert_eq!(parse_selection(" 2,3\n", 3), Some(vec![1, 2]));
+        assert_eq!(parse_selection("\n", 3), Some(vec![]));
+        assert_eq!(parse_selection("4", 3), None);
+        assert_eq!(parse_selection("0", 3), None);
+        assert_eq!(parse_selection("a", 3), None);
+    }
+
+    #[test]
+    fn test_pick() {
+        let paths = [
+            PathBuf::from("tests/test_parse_ok.mnemo"),
+            PathBuf::from("tests/test_tags.mnemo"),
+        ];
+        let mut suite = Suite::read_from_files(&paths, None).unwrap();
+        let mut out = vec![];
+        suite.pick(&mut "9\n2\n".as_bytes(), &mut out);
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("1) tests/test_parse_ok.mnemo: 3 due, 2 new"));
+        assert!(out.contains("2) tests/test_tags.mnemo: 0 due, 4 new"));
+        assert!(out.contains("invalid selection."));
+        assert_eq!(suite.decks.len(), 1);
+        assert_eq!(suite.decks[0].path, paths[1]);
+
+        let mut suite = Suite::read_from_files(&paths, None).unwrap();
+        suite.pick(&mut "\n".as_bytes(), &mut vec![]);
+        assert_eq!(suite.decks.len(), 2);
+    }
+
+    #[test]
+    fn test_retention() {
+        let suite =
+            Suite::read_from_files(&[PathBuf::from("tests/test_retention.mnemo")], None).unwrap();
+        assert_eq!(suite.retention(), Some((0.75, 8)));
+        let suite =
+            Suite::read_from_files(&[PathBuf::from("tests/test_parse_ok.mnemo")], None).unwrap();
+        assert_eq!(suite.retention(), None);
+    }
+
+    #[test]
+    fn test_tag_counts() {
+        let suite = Suite::read_from_files(
+            &[
+                PathBuf::from("tests/test_tags.mnemo"),
+                PathBuf::from("tests/test_tags.mnemo"),
+            ],
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            suite.tag_counts(),
+            vec![
+                ("europe".to_string(), 6),
+                ("(untagged)".to_string(), 2),
+                ("nordic".to_string(), 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_next_due() {
+        const OUT: &str = "tests/test_next_due_copy.txt";
+        let _ = std::fs::remove_file(OUT);
+
+        // card 2 comes due first, at the start of its due date.
+        let suite =
+            Suite::read_from_files(&[PathBuf::from("tests/test_next_due.mnemo")], None).unwrap();
+        suite.write_next_due(Path::new(OUT)).unwrap();
+        let due = std::fs::read_to_string(OUT).unwrap();
+        assert_eq!(due, format!("{}\n", suite.decks[0].status[&2].due_at()));
+        let due = due.trim().parse::<i64>().unwrap();
+        assert!(due <= 3000000000 && due > 3000000000 - 86400);
+
+        // something is due already.
+        let suite = Suite::read_from_files(&[PathBuf::from("tests/test_pin.mnemo")], None).unwrap();
+        suite.write_next_due(Path::new(OUT)).unwrap();
+        assert_eq!(std::fs::read_to_string(OUT).unwrap(), "0\n");
+
+        // nothing is scheduled.
+        let suite =
+            Suite::read_from_files(&[PathBuf::from("tests/test_tags.mnemo")], None).unwrap();
+        suite.write_next_due(Path::new(OUT)).unwrap();
+        assert_eq!(std::fs::read_to_string(OUT).unwrap(), "");
+
+        std::fs::remove_file(OUT).unwrap();
+    }
+
+    #[test]
+    fn test_export_study_sheet() {
+        let paths = ["tests/test_tags.mnemo", "tests/test_pin.mnemo"].map(PathBuf::from);
+        let suite = Suite::read_from_files(&paths, None).unwrap();
+        let mut out = vec![];
+        suite.export_study_sheet(&mut out, false).unwrap();
+        let html = String::from_utf8(out).unwrap();
+        assert_eq!(html.matches("<!DOCTYPE html>").count(), 1);
+        assert_eq!(html.matches("</html>").count(), 1);
+        assert_eq!(html.matches("<section>").count(), 2);
+        assert!(html.contains("<title>tests/test_tags.mnemo, tests/test_pin.mnemo</title>"));
+        assert!(
+            html.find("<h1>tests/test_tags.mnemo</h1>").unwrap()
+                < html.find("<h1>tests/test_pin.mnemo</h1>").unwrap()
+        );
+        assert!(html.ends_with("</section>\n</body></html>\n"));
+    }
+
+    #[test]
+    fn test_inspect_table() {
+        let paths = [
+            "tests/test_tags.mnemo",
+            "tests/test_pin.mnemo",
+            "tests/test_review_done.mnemo",
+        ]
+        .map(PathBuf::from);
+        let suite = Suite::read_from_files(&paths, None).unwrap();
+        let table = suite.inspect_table(SortBy::Due);
+        let lines = table.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![
+                "deck                            due    new  waiting   done  total",
+                "tests/test_pin.mnemo              2      2        0      1      5",
+                "tests/test_review_done.mnemo      1      1        0      3      5",
+                "tests/test_tags.mnemo             0      4        0      0      4",
+            ]
+        );
+
+        let table = suite.inspect_table(SortBy::New);
+        let order = table
+            .lines()
+            .skip(1)
+            .map(|line| line.split(' ').next().unwrap());
+        assert_eq!(
+            order.collect::<Vec<_>>(),
+            [
+                "tests/test_tags.mnemo",
+                "tests/test_pin.mnemo",
+                "tests/test_review_done.mnemo"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_count() {
+        let paths = [
+            "tests/test_tags.mnemo",
+            "tests/test_pin.mnemo",
+            "tests/test_review_done.mnemo",
+        ]
+        .map(PathBuf::from);
+        let suite = Suite::read_from_files(&paths, None).unwrap();
+        let counts =
+            ["due", "new", "done", "total"].map(|bucket| suite.count(bucket.parse().unwrap()));
+        assert_eq!(counts, [3, 7, 4, 14]);
+        assert!("waiting".parse::<Bucket>().is_err());
+    }
+
+    #[test]
+    fn test_explain() {
+        colored::control::set_override(false);
+        let mut suite =
+            Suite::read_from_files(&[PathBuf::from("tests/test_next_due.mnemo")], None).unwrap();
+        let opts = PlayOptions::default();
+        let explain = |suite: &mut Suite, id| {
+            let mut out = vec![];
+            let found = suite.explain(id, &opts, &mut out, "%Y-%m-%d");
+            (found, String::from_utf8(out).unwrap())
+        };
+
+        let (found, out) = explain(&mut suite, 2);
+        assert!(found);
+        let due = suite.decks[0].status[&2].due_date().format("%Y-%m-%d");
+        assert!(out.starts_with("tests/test_next_due.mnemo::#2: Oslo\nwaiting\n"));
+        assert!(out.ends_with(&format!("not played: not due until {}.\n", due)));
+
+        // max_new is 0 by default.
+        let (_, out) = explain(&mut suite, 3);
+        assert_eq!(
+            out,
+            "tests/test_next_due.mnemo::#3: Helsinki\nnew\n\
+             not played: earlier new cards take up the new card cap.\n"
+        );
+        let opts = PlayOptions {
+            max_new: 1,
+            ..PlayOptions::default()
+        };
+        let mut out = vec![];
+        suite.explain(3, &opts, &mut out, "%Y-%m-%d");
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .ends_with("in today's queue.\n"));
+
+        assert_eq!(explain(&mut suite, 9), (false, String::new()));
+    }
+
+    #[test]
+    fn test_list_done() {
+        let paths = ["tests/test_pin.mnemo", "tests/test_review_done.mnemo"].map(PathBuf::from);
+        let suite = Suite::read_from_files(&paths, None).unwrap();
+        assert_eq!(
+            suite.list_done(),
+            vec![
+                (1, 1, 100000000 - 64 * 86400),
+                (1, 2, 200000000 - 64 * 86400),
+                (1, 4, 400000000 - 128 * 86400),
+                (0, 4, 4000000000 - 64 * 86400)
+            ]
+        );
+        let suite =
+            Suite::read_from_files(&[PathBuf::from("tests/test_tags.mnemo")], None).unwrap();
+        assert!(suite.list_done().is_empty());
+    }
+
+    #[test]
+    fn test_accuracy_thresholds() {
+        let default = AccuracyThresholds::default();
+        assert_eq!(default.color(79.9), Color::Red);
+        assert_eq!(default.color(80.0), Color::Yellow);
+        assert_eq!(default.color(95.0), Color::Yellow);
+        assert_eq!(default.color(95.1), Color::Green);
+
+        let lenient = "70, 90".parse::<AccuracyThresholds>().unwrap();
+        for (percentage, color) in [
+            (0.0, Color::Red),
+            (69.9, Color::Red),
+            (75.0, Color::Yellow),
+            (90.0, Color::Yellow),
+            (92.0, Color::Green),
+            (100.0, Color::Green),
+        ] {
+            assert_eq!(lenient.color(percentage), color);
+        }
+
+        assert!("90,70".parse::<AccuracyThresholds>().is_err());
+        assert!("70,101".parse::<AccuracyThresholds>().is_err());
+        assert!("70".parse::<AccuracyThresholds>().is_err());
+    }
+
+    #[test]
+    fn test_format_minimal() {
+        let suite = Suite::read_from_files(
+            &parse_files(&[PathBuf::from("tests/test_combine_b.suite")], false).unwrap(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(format_minimal(suite.due_and_new()), "0d/14n");
+        let suite = Suite::read_from_files(
+            &[
+                PathBuf::from("tests/test_parse_ok.mnemo"),
+                PathBuf::from("tests/test_tags.mnemo"),
+            ],
+            None,
+        )
+        .unwrap();
+        assert_eq!(format_minimal(suite.due_and_new()), "3d/6n");
+    }
+
+    #[test]
+    fn test_parse_files_directory() {
+        const DIR: &str = "tests/test_parse_files_directory";
+        let _ = std::fs::remove_dir_all(DIR);
+        std::fs::create_dir_all(format!("{}/sub", DIR)).unwrap();
+        std::fs::create_dir_all(format!("{}/empty", DIR)).unwrap();
+        for file in [
+            "b.mnemo",
+            "a.mnemo",
+            "a.mnemo.log",
+            "notes.txt",
+            "sub/c.mnemo",
+        ] {
+            std::fs::write(format!("{}/{}", DIR, file), "").unwrap();
+        }
+        let dir = PathBuf::from(DIR);
+
+        assert_eq!(
+            parse_files(std::slice::from_ref(&dir), false).unwrap(),
+            vec![dir.join("a.mnemo"), dir.join("b.mnemo")]
+        );
+        assert_eq!(
+            parse_files(std::slice::from_ref(&dir), true).unwrap(),
+            vec![
+                dir.join("a.mnemo"),
+                dir.join("b.mnemo"),
+                dir.join("sub/c.mnemo")
+            ]
+        );
+        assert_eq!(
+            parse_files(&[dir.join("empty")], true),
+            Err((dir.join("empty"), DeckErr::NoDecksInDirectory))
+        );
+
+        // a symlink back up the tree is searched once.
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("..", format!("{}/sub/up", DIR)).unwrap();
+            assert_eq!(
+                parse_files(std::slice::from_ref(&dir), true).unwrap().len(),
+                3
+            );
+        }
+
+        std::fs::remove_dir_all(DIR).unwrap();
+    }
+
+    #[test]
+    fn test_find_suite() {
+        assert_eq!(find_suite(&[PathBuf::from("tests/test_tags.mnemo")]), None);
+        assert_eq!(
+            find_suite(&[PathBuf::from("tests/test_combine_a.suite")]),
+            Some(&PathBuf::from("tests/test_combine_a.suite"))
+        );
+    }
+
+    #[test]
+    fn test_parse_files_combines_suites() {
+        let paths = parse_files(
+            &[
+                PathBuf::from("tests/test_combine_a.suite"),
+                PathBuf::from("tests/test_combine_b.suite"),
+                PathBuf::from("tests/test_tags.mnemo"),
+            ],
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("tests/test_tags.mnemo"),
+                PathBuf::from("tests/test_priority.mnemo"),
+                PathBuf::from("tests/test_relearn_gap.mnemo"),
+            ]
+        );
+    }
+}